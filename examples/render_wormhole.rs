@@ -0,0 +1,17 @@
+//
+// Render the view through an Ellis wormhole to a PPM image, without
+// opening a window (or needing a GPU):
+//
+//   cargo run --release --example render_wormhole -- wormhole.ppm
+//
+
+use curved_space::wormhole;
+
+fn main() -> std::io::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "wormhole.ppm".to_string());
+    wormhole::render_to_file(&wormhole::Camera::default(), &path)?;
+    println!("Wrote wormhole view to {}", path);
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//
+// bvp.rs: Finding the geodesic(s) between two given points. This is
+// a boundary-value problem, where the rest of the tracer solves
+// initial-value problems.
+//
+// The main approach is the shooting method: Fire geodesics from A at
+// a range of angles, look for neighbouring angles where the ray
+// passes either side of B, and bisect on the angle until the ray hits
+// B. Each such bracket gives a different geodesic (e.g. either way
+// around the wormhole).
+//
+// If shooting finds nothing, we fall back to relaxing a path between
+// A and B: Repeatedly pull each point towards the midpoint of its
+// neighbours and put it back on the surface, respacing the points
+// evenly as we go (the "string method"). This shortens the path until
+// it's locally minimal, i.e. a geodesic.
+//
+
+use crate::polyline;
+use crate::vec3::*;
+
+use std::f64::consts::PI;
+
+// Number of angles to try when looking for brackets.
+const SHOTS: usize = 180;
+// Iterations of bisection on each bracket.
+const BISECT_ITER: usize = 40;
+// How close a ray must get to B to count as a hit.
+const HIT_TOL: f64 = 1.0e-4;
+// Longest ray fired.
+const MAX_LEN: f64 = 8.0;
+
+// Number of points in, and maximum iterations of, path relaxation.
+// It runs in the UI thread, so stop once no point moves more than
+// RELAX_TOL in a sweep, which is well under the tracer's step size.
+const RELAX_POINTS: usize = 64;
+const RELAX_ITER: usize = 1000;
+const RELAX_TOL: f64 = 1.0e-6;
+
+// What the solver needs from the tracer.
+pub trait Shooter {
+    // Trace a geodesic from p, starting in tangent direction v, until
+    // it leaves the domain or is max_len long.
+    fn shoot(&self, p: &Vec3, v: &Vec3, max_len: f64) -> Vec<Vec3>;
+    // The nearest point on the surface to p.
+    fn project(&self, p: &Vec3) -> Option<Vec3>;
+    // A (not necessarily unit) normal to the surface at p.
+    fn normal(&self, p: &Vec3) -> Vec3;
+}
+
+#[derive(Clone, Debug)]
+pub struct Geodesic {
+    pub points: Vec<Vec3>,
+    pub length: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    // Shortest first.
+    pub geodesics: Vec<Geodesic>,
+    // Whether the result came from the relaxation fallback rather than
+    // shooting.
+    pub relaxed: bool,
+}
+
+pub fn solve(s: &impl Shooter, a: &Vec3, b: &Vec3) -> Solution {
+    let mut geodesics = shoot_all(s, a, b);
+    if !geodesics.is_empty() {
+        geodesics.sort_by(|g1, g2| g1.length.total_cmp(&g2.length));
+        return Solution {
+            geodesics,
+            relaxed: false,
+        };
+    }
+    Solution {
+        geodesics: relax(s, a, b).into_iter().collect(),
+        relaxed: true,
+    }
+}
+
+// Solutions kept between updates, keyed by their end points, so that
+// only geodesics whose ends moved are solved again. The key doesn't
+// cover the surface, so start a new cache when that changes.
+#[derive(Default)]
+pub struct Cache {
+    entries: Vec<(Vec3, Vec3, Solution)>,
+}
+
+impl Cache {
+    // The solutions between each pair of points, in order, reusing
+    // those already found for the same pair. The result holds just
+    // these, so pairs no longer wanted are dropped.
+    pub fn update(&self, s: &impl Shooter, pairs: &[(Vec3, Vec3)]) -> Cache {
+        let entries = pairs
+            .iter()
+            .map(|(a, b)| {
+                let cached = self
+                    .entries
+                    .iter()
+                    .find(|(ca, cb, _)| ca == a && cb == b)
+                    .map(|(_, _, sol)| sol.clone());
+                let sol = cached.unwrap_or_else(|| solve(s, a, b));
+                (a.clone(), b.clone(), sol)
+            })
+            .collect();
+        Cache { entries }
+    }
+
+    pub fn solutions(&self) -> impl Iterator<Item = &Solution> {
+        self.entries.iter().map(|(_, _, sol)| sol)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Shooting.
+//
+
+// Where a ray fired at a given angle passes B.
+struct Shot {
+    // Distance from B at closest approach, signed by which side of B
+    // the ray passes.
+    miss: f64,
+    closest: polyline::Closest,
+    path: Vec<Vec3>,
+}
+
+fn shoot_all(s: &impl Shooter, a: &Vec3, b: &Vec3) -> Vec<Geodesic> {
+    // Basis for the tangent plane at A, with angle zero pointing
+    // roughly at B.
+    let n = s.normal(a).norm();
+    let towards = b.sub(a);
+    let mut e1 = towards.sub(&n.scale(towards.dot(&n)));
+    if e1.len() < 1.0e-12 {
+        // B is straight above or below A. Pick anything.
+        e1 = n.cross(&Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        if e1.len() < 1.0e-12 {
+            e1 = n.cross(&Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            });
+        }
+    }
+    let e1 = e1.norm();
+    let e2 = n.cross(&e1);
+
+    let fire = |theta: f64| -> Option<Shot> {
+        let dir = e1.scale(theta.cos()).add(&e2.scale(theta.sin()));
+        let path = s.shoot(a, &dir, MAX_LEN);
+        let closest = polyline::closest_point(&path, b)?;
+        // If the closest approach is at the start, or at the end
+        // short of B, the ray never passes B, so there's no meaningful
+        // side. A ray that ends right at B (as when B is about
+        // MAX_LEN away) still hits it.
+        let at_end = closest.point.sub(path.last()?).len() <= 0.0;
+        if closest.arc_len <= 0.0 || (at_end && closest.dist >= HIT_TOL) {
+            return None;
+        }
+        let tangent = path[closest.segment + 1].sub(&path[closest.segment]);
+        let side = s
+            .normal(&closest.point)
+            .dot(&tangent.cross(&b.sub(&closest.point)));
+        Some(Shot {
+            miss: closest.dist.copysign(side),
+            closest,
+            path,
+        })
+    };
+
+    let angles = (0..SHOTS)
+        .map(|idx| -PI + 2.0 * PI * idx as f64 / SHOTS as f64)
+        .collect::<Vec<_>>();
+    let shots = angles.iter().map(|theta| fire(*theta)).collect::<Vec<_>>();
+
+    let mut geodesics = Vec::new();
+    for idx in 0..SHOTS {
+        let next = (idx + 1) % SHOTS;
+        let (Some(lo_shot), Some(hi_shot)) = (&shots[idx], &shots[next]) else {
+            continue;
+        };
+        if lo_shot.miss.signum() == hi_shot.miss.signum() {
+            continue;
+        }
+
+        let mut lo = angles[idx];
+        let mut hi = if next == 0 { PI } else { angles[next] };
+        let lo_sign = lo_shot.miss.signum();
+        let mut best = None;
+        for _ in 0..BISECT_ITER {
+            let mid = (lo + hi) * 0.5;
+            let Some(shot) = fire(mid) else {
+                break;
+            };
+            if shot.miss.signum() == lo_sign {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+            best = Some(shot);
+        }
+
+        // A sign change can also come from the ray jumping across B
+        // (e.g. at the edge of the domain), rather than passing
+        // through it, so check we actually hit.
+        if let Some(shot) = best.filter(|shot| shot.miss.abs() < HIT_TOL) {
+            let mut points = shot.path[..=shot.closest.segment].to_vec();
+            points.push(shot.closest.point.clone());
+            points.push(b.clone());
+            geodesics.push(Geodesic {
+                points,
+                length: shot.closest.arc_len + shot.closest.dist,
+            });
+        }
+    }
+    geodesics
+}
+
+////////////////////////////////////////////////////////////////////////
+// Relaxation.
+//
+
+fn relax(s: &impl Shooter, a: &Vec3, b: &Vec3) -> Option<Geodesic> {
+    // Start with the straight line, pushed onto the surface.
+    let mut points = (0..RELAX_POINTS)
+        .map(|idx| {
+            let t = idx as f64 / (RELAX_POINTS - 1) as f64;
+            s.project(&a.add(&b.sub(a).scale(t)))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    points[0] = a.clone();
+    points[RELAX_POINTS - 1] = b.clone();
+
+    for _ in 0..RELAX_ITER {
+        let mut moved: f64 = 0.0;
+        for idx in 1..RELAX_POINTS - 1 {
+            let mid = points[idx - 1].add(&points[idx + 1]).scale(0.5);
+            let new_p = s.project(&mid)?;
+            moved = moved.max(new_p.sub(&points[idx]).len());
+            points[idx] = new_p;
+        }
+
+        // Respace evenly along the path, to stop points bunching up.
+        let lens = polyline::arc_lengths(&points);
+        let total = lens[RELAX_POINTS - 1];
+        for idx in 1..RELAX_POINTS - 1 {
+            let target = total * idx as f64 / (RELAX_POINTS - 1) as f64;
+            let p = polyline::point_at(&points, &lens, target);
+            points[idx] = s.project(&p)?;
+        }
+
+        if moved < RELAX_TOL {
+            break;
+        }
+    }
+
+    let length = polyline::arc_lengths(&points)[RELAX_POINTS - 1];
+    Some(Geodesic { points, length })
+}
@@ -0,0 +1,171 @@
+//
+// camera.rs: The camera the surface is viewed through, orbiting a
+// target point. It's all done on the CPU, and handed to the vertex
+// shader as a single matrix.
+//
+// Z is up. Turn swings the camera around the vertical axis through
+// the target, and tilt raises it above the XY plane, so that with no
+// turn or tilt it looks along +Y.
+//
+
+use crate::mat4::*;
+use crate::vec3::*;
+
+// The default view fits the -1..1 cube in, whichever way it's turned.
+const DEFAULT_TILT: f64 = 30.0;
+const DEFAULT_DISTANCE: f64 = 2.5;
+const DEFAULT_FOV: f64 = 60.0;
+const DEFAULT_NEAR: f64 = 0.01;
+const DEFAULT_FAR: f64 = 100.0;
+
+const MIN_DISTANCE: f64 = 0.01;
+const MAX_DISTANCE: f64 = 50.0;
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub target: Vec3,
+    pub distance: f64,
+    // Angles in degrees.
+    pub tilt: f64,
+    pub turn: f64,
+    // Field of view across the narrower side of the window.
+    pub fov: f64,
+    // Clipping planes, as distances from the camera.
+    pub near: f64,
+    pub far: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            target: Vec3::zero(),
+            distance: DEFAULT_DISTANCE,
+            tilt: DEFAULT_TILT,
+            turn: 0.0,
+            fov: DEFAULT_FOV,
+            near: DEFAULT_NEAR,
+            far: DEFAULT_FAR,
+        }
+    }
+}
+
+impl Camera {
+    // The camera's position, and its right, up and forward directions.
+    fn frame(&self) -> (Vec3, Vec3, Vec3, Vec3) {
+        let (tilt, turn) = (self.tilt.to_radians(), self.turn.to_radians());
+        let forward = Vec3 {
+            x: turn.sin() * tilt.cos(),
+            y: turn.cos() * tilt.cos(),
+            z: -tilt.sin(),
+        };
+        // Worked out from the turn alone, so it's still good looking
+        // straight down.
+        let right = Vec3 {
+            x: turn.cos(),
+            y: -turn.sin(),
+            z: 0.0,
+        };
+        let up = right.cross(&forward);
+        let eye = self.target.sub(&forward.scale(self.distance));
+        (eye, right, up, forward)
+    }
+
+    // Scale factors taking X and Y in view space to the window, for a
+    // window of the given size.
+    fn scales(&self, width: u32, height: u32) -> (f64, f64) {
+        let aspect = width as f64 / height.max(1) as f64;
+        let f = (self.fov.to_radians() * 0.5).tan().recip();
+        (f * aspect.recip().min(1.0), f * aspect.min(1.0))
+    }
+
+    // The transform from world to view space.
+    pub fn view(&self) -> Mat4 {
+        let (eye, right, up, forward) = self.frame();
+        Mat4::view(&eye, &right, &up, &forward)
+    }
+
+    // The full transform from world to clip space, for a window of the
+    // given size.
+    pub fn transform(&self, width: u32, height: u32) -> Mat4 {
+        let (x_scale, y_scale) = self.scales(width, height);
+        Mat4::perspective(x_scale, y_scale, self.near, self.far).mul(&self.view())
+    }
+
+    // The line through the camera and the pixel at (x, y) in a window
+    // of the given size, measured from the top left: Where it starts,
+    // and its direction.
+    pub fn ray(&self, x: f64, y: f64, width: u32, height: u32) -> (Vec3, Vec3) {
+        let (eye, right, up, forward) = self.frame();
+        let (x_scale, y_scale) = self.scales(width, height);
+        let ndc_x = 2.0 * x / width.max(1) as f64 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height.max(1) as f64;
+        let dir = forward
+            .add(&right.scale(ndc_x / x_scale))
+            .add(&up.scale(ndc_y / y_scale));
+        (eye, dir.norm())
+    }
+
+    // Swing the camera around the target, by angles in degrees.
+    pub fn orbit(&mut self, d_turn: f64, d_tilt: f64) {
+        self.turn += d_turn;
+        if self.turn > 180.0 {
+            self.turn -= 360.0;
+        } else if self.turn < -180.0 {
+            self.turn += 360.0;
+        }
+        self.tilt = (self.tilt + d_tilt).clamp(-90.0, 90.0);
+    }
+
+    // Move towards the target, dividing the distance by factor.
+    pub fn zoom(&mut self, factor: f64) {
+        self.distance = (self.distance / factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    // Slide the camera and target sideways, so that the scene follows
+    // a drag of (dx, dy) pixels across a window of the given size.
+    pub fn pan(&mut self, dx: f64, dy: f64, width: u32, height: u32) {
+        let (_, right, up, _) = self.frame();
+        // World units per pixel, at the target's depth.
+        let (_, y_scale) = self.scales(width, height);
+        let per_pixel = 2.0 * self.distance / (y_scale * height.max(1) as f64);
+        self.target = self
+            .target
+            .sub(&right.scale(dx * per_pixel))
+            .add(&up.scale(dy * per_pixel));
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.tilt, -90.0..=90.0).text("Tilt"));
+        ui.add(egui::Slider::new(&mut self.turn, -180.0..=180.0).text("Turn"));
+        egui::CollapsingHeader::new("Camera").show(ui, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.distance, MIN_DISTANCE..=MAX_DISTANCE)
+                    .logarithmic(true)
+                    .text("Distance"),
+            );
+            ui.add(egui::Slider::new(&mut self.fov, 5.0..=120.0).text("Field of view"));
+            ui.add(
+                egui::Slider::new(&mut self.near, 0.001..=1.0)
+                    .logarithmic(true)
+                    .text("Near"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.far, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("Far"),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Target");
+                for v in [&mut self.target.x, &mut self.target.y, &mut self.target.z] {
+                    ui.add(egui::DragValue::new(v).speed(0.01));
+                }
+            });
+            if ui.button("Reset").clicked() {
+                *self = Camera::default();
+            }
+            ui.label("Drag to orbit, right-drag to pan, scroll to zoom.");
+            ui.label("Shift-click to move the ray origin, and drag to aim.");
+            ui.label("Keys: Arrows orbit, +/- zoom, R resets.");
+        });
+    }
+}
@@ -0,0 +1,56 @@
+//
+// colour.rs: Colour maps, for showing scalar fields on the surface.
+//
+
+// A perceptually-uniform sequential map (an approximation of
+// viridis), from dark purple at 0 to yellow at 1. Values outside
+// [0, 1] are clamped.
+pub fn sequential(t: f64) -> [f32; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.267, 0.005, 0.329],
+        [0.230, 0.322, 0.546],
+        [0.128, 0.567, 0.551],
+        [0.369, 0.789, 0.383],
+        [0.993, 0.906, 0.144],
+    ];
+    interpolate(&STOPS, t)
+}
+
+// A diverging map for signed values (an approximation of
+// "coolwarm"), from blue at -1 through grey at 0 to red at 1. Values
+// outside [-1, 1] are clamped.
+pub fn diverging(t: f64) -> [f32; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.230, 0.299, 0.754],
+        [0.552, 0.690, 0.996],
+        [0.865, 0.865, 0.865],
+        [0.958, 0.604, 0.482],
+        [0.706, 0.016, 0.150],
+    ];
+    interpolate(&STOPS, (t + 1.0) * 0.5)
+}
+
+// A cyclic map for angles, going once around the hue wheel as t
+// goes from 0 to 1, and repeating outside that.
+pub fn cyclic(t: f64) -> [f32; 3] {
+    const STOPS: [[f32; 3]; 7] = [
+        [0.9, 0.2, 0.2],
+        [0.9, 0.9, 0.2],
+        [0.2, 0.9, 0.2],
+        [0.2, 0.9, 0.9],
+        [0.2, 0.2, 0.9],
+        [0.9, 0.2, 0.9],
+        [0.9, 0.2, 0.2],
+    ];
+    interpolate(&STOPS, t.rem_euclid(1.0))
+}
+
+// Piecewise-linear interpolation between evenly-spaced colour stops.
+fn interpolate(stops: &[[f32; 3]], t: f64) -> [f32; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let scaled = t * (stops.len() - 1) as f64;
+    let idx = (scaled as usize).min(stops.len() - 2);
+    let fract = (scaled - idx as f64) as f32;
+    let (c0, c1) = (stops[idx], stops[idx + 1]);
+    [0, 1, 2].map(|i| c0[i] + (c1[i] - c0[i]) * fract)
+}
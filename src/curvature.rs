@@ -0,0 +1,33 @@
+//
+// curvature.rs: Gaussian and mean curvature of an implicit surface
+// f(p) = 0, from the gradient and Hessian of f (following Goldman,
+// "Curvature formulas for implicit curves and surfaces", 2005).
+//
+
+use crate::geodesic::Field;
+use crate::vec3::*;
+
+#[derive(Clone, Debug)]
+pub struct Curvature {
+    // Product of the principal curvatures. Positive where the surface
+    // is locally dome-shaped, negative where it's saddle-shaped.
+    pub gaussian: f64,
+    // Average of the principal curvatures, signed so that it's
+    // positive where the surface curves away from grad f (e.g. +1 on
+    // the unit sphere x^2 + y^2 + z^2 - 1 = 0).
+    pub mean: f64,
+}
+
+pub fn at(field: &impl Field, p: &Vec3) -> Curvature {
+    let grad = field.gradient(p);
+    let hessian = field.hessian(p);
+    let len_sq = grad.dot(&grad);
+
+    // K = grad^T adj(H) grad / |grad|^4
+    let gaussian = hessian.adjugate().quad_form(&grad) / (len_sq * len_sq);
+    // H = (|grad|^2 tr(H) - grad^T H grad) / (2 |grad|^3)
+    let mean =
+        (len_sq * hessian.trace() - hessian.quad_form(&grad)) / (2.0 * len_sq * len_sq.sqrt());
+
+    Curvature { gaussian, mean }
+}
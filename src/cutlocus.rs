@@ -0,0 +1,75 @@
+//
+// cutlocus.rs: Estimate the cut locus of a point, from a dense set of
+// geodesics leaving it.
+//
+// A geodesic from a point stops being the shortest path back to it
+// once it passes the cut locus. Past that, some other geodesic gets
+// there first. We can't find the other geodesic exactly, but if
+// another traced path passes close by, having covered so little
+// distance that it plus a short hop beats ours, ours can't be
+// minimising any more.
+//
+// The first such point along each geodesic is at or just past its
+// cut point, as long as the paths are dense enough for another one to
+// pass within `reach` of it.
+//
+
+use std::collections::HashMap;
+
+use crate::polyline;
+use crate::vec3::*;
+
+// For each path, which must all start from the same point, the first
+// point at which a shorter route to it is found via another path
+// passing within `reach`. Shorter means shorter by more than `slack`,
+// to allow for errors in tracing.
+pub fn cut_points(paths: &[Vec<Vec3>], reach: f64, slack: f64) -> Vec<Option<Vec3>> {
+    let lens = paths
+        .iter()
+        .map(|path| polyline::arc_lengths(path))
+        .collect::<Vec<_>>();
+
+    // Bucket the points into cubes of side `reach`, so that anything
+    // within reach of a point is in its cube or a neighbour.
+    let cell = |p: &Vec3| {
+        (
+            (p.x / reach).floor() as i64,
+            (p.y / reach).floor() as i64,
+            (p.z / reach).floor() as i64,
+        )
+    };
+    let mut cells: HashMap<(i64, i64, i64), Vec<(usize, usize)>> = HashMap::new();
+    for (path_idx, path) in paths.iter().enumerate() {
+        for (idx, p) in path.iter().enumerate() {
+            cells.entry(cell(p)).or_default().push((path_idx, idx));
+        }
+    }
+
+    let beaten = |p: &Vec3, s: f64| {
+        let (i, j, k) = cell(p);
+        (-1..=1).any(|di| {
+            (-1..=1).any(|dj| {
+                (-1..=1).any(|dk| {
+                    cells.get(&(i + di, j + dj, k + dk)).is_some_and(|points| {
+                        points.iter().any(|(path_idx, idx)| {
+                            let q = &paths[*path_idx][*idx];
+                            let hop = q.sub(p).len();
+                            hop < reach && lens[*path_idx][*idx] + hop < s - slack
+                        })
+                    })
+                })
+            })
+        })
+    };
+
+    paths
+        .iter()
+        .zip(lens.iter())
+        .map(|(path, lens)| {
+            path.iter()
+                .zip(lens.iter())
+                .find(|(p, s)| beaten(p, **s))
+                .map(|(p, _)| p.clone())
+        })
+        .collect()
+}
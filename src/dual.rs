@@ -0,0 +1,440 @@
+//
+// dual.rs: Forward-mode automatic differentiation. Functions written
+// generically over `Scalar` can be evaluated with plain f64s for
+// their value, with `Dual` numbers to get an exact gradient as well,
+// or with `HyperDual` numbers to get an exact Hessian too.
+//
+// Derivatives are taken with respect to three variables (x, y and
+// z), which is all the surfaces need.
+//
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::mat3::*;
+use crate::vec3::*;
+
+pub trait Scalar:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+{
+    fn from_f64(v: f64) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn powf(self, e: Self) -> Self;
+}
+
+impl Scalar for f64 {
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+
+    fn powf(self, e: Self) -> Self {
+        f64::powf(self, e)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// HyperDual: A value, its gradient and its Hessian. Every operation
+// is expressed through `chain`, which applies a scalar function
+// given its first and second derivatives, and the product rule.
+//
+// `Dual` below is the same thing without the second derivatives.
+//
+
+#[derive(Clone, Copy, Debug)]
+pub struct HyperDual {
+    pub v: f64,
+    pub d: [f64; 3],
+    pub h: [[f64; 3]; 3],
+}
+
+impl HyperDual {
+    pub fn constant(v: f64) -> HyperDual {
+        HyperDual {
+            v,
+            d: [0.0; 3],
+            h: [[0.0; 3]; 3],
+        }
+    }
+
+    // The independent variable with the given index.
+    pub fn var(v: f64, idx: usize) -> HyperDual {
+        let mut x = HyperDual::constant(v);
+        x.d[idx] = 1.0;
+        x
+    }
+
+    // g(self), where g has value g0, derivative g1 and second
+    // derivative g2 at self.v.
+    fn chain(self, g0: f64, g1: f64, g2: f64) -> HyperDual {
+        let mut r = HyperDual::constant(g0);
+        for i in 0..3 {
+            r.d[i] = g1 * self.d[i];
+            for j in 0..3 {
+                r.h[i][j] = g1 * self.h[i][j] + g2 * self.d[i] * self.d[j];
+            }
+        }
+        r
+    }
+
+    fn is_constant(&self) -> bool {
+        self.d == [0.0; 3] && self.h == [[0.0; 3]; 3]
+    }
+}
+
+impl Add for HyperDual {
+    type Output = HyperDual;
+    fn add(self, rhs: HyperDual) -> HyperDual {
+        let mut r = self;
+        r.v += rhs.v;
+        for i in 0..3 {
+            r.d[i] += rhs.d[i];
+            for j in 0..3 {
+                r.h[i][j] += rhs.h[i][j];
+            }
+        }
+        r
+    }
+}
+
+impl Sub for HyperDual {
+    type Output = HyperDual;
+    fn sub(self, rhs: HyperDual) -> HyperDual {
+        self + -rhs
+    }
+}
+
+impl Mul for HyperDual {
+    type Output = HyperDual;
+    fn mul(self, rhs: HyperDual) -> HyperDual {
+        let mut r = HyperDual::constant(self.v * rhs.v);
+        for i in 0..3 {
+            r.d[i] = self.d[i] * rhs.v + self.v * rhs.d[i];
+            for j in 0..3 {
+                r.h[i][j] = self.h[i][j] * rhs.v
+                    + self.d[i] * rhs.d[j]
+                    + self.d[j] * rhs.d[i]
+                    + self.v * rhs.h[i][j];
+            }
+        }
+        r
+    }
+}
+
+impl Div for HyperDual {
+    type Output = HyperDual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: HyperDual) -> HyperDual {
+        let inv = rhs.v.recip();
+        self * rhs.chain(inv, -inv * inv, 2.0 * inv * inv * inv)
+    }
+}
+
+impl Neg for HyperDual {
+    type Output = HyperDual;
+    fn neg(self) -> HyperDual {
+        self.chain(-self.v, -1.0, 0.0)
+    }
+}
+
+impl Add<f64> for HyperDual {
+    type Output = HyperDual;
+    fn add(self, rhs: f64) -> HyperDual {
+        HyperDual {
+            v: self.v + rhs,
+            ..self
+        }
+    }
+}
+
+impl Sub<f64> for HyperDual {
+    type Output = HyperDual;
+    fn sub(self, rhs: f64) -> HyperDual {
+        self + -rhs
+    }
+}
+
+impl Mul<f64> for HyperDual {
+    type Output = HyperDual;
+    fn mul(self, rhs: f64) -> HyperDual {
+        self.chain(self.v * rhs, rhs, 0.0)
+    }
+}
+
+impl Div<f64> for HyperDual {
+    type Output = HyperDual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: f64) -> HyperDual {
+        self * rhs.recip()
+    }
+}
+
+impl Scalar for HyperDual {
+    fn from_f64(v: f64) -> Self {
+        HyperDual::constant(v)
+    }
+
+    fn sin(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        self.chain(s, c, -s)
+    }
+
+    fn cos(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        self.chain(c, -s, -c)
+    }
+
+    fn exp(self) -> Self {
+        let e = self.v.exp();
+        self.chain(e, e, e)
+    }
+
+    fn ln(self) -> Self {
+        let inv = self.v.recip();
+        self.chain(self.v.ln(), inv, -inv * inv)
+    }
+
+    fn sqrt(self) -> Self {
+        let s = self.v.sqrt();
+        self.chain(s, 0.5 / s, -0.25 / (s * self.v))
+    }
+
+    fn abs(self) -> Self {
+        let sign = self.v.signum();
+        self.chain(self.v.abs(), sign, 0.0)
+    }
+
+    fn powf(self, e: Self) -> Self {
+        if e.is_constant() {
+            // Handles negative bases with integer exponents.
+            let n = e.v;
+            self.chain(
+                self.v.powf(n),
+                power_term(n, self.v, n - 1.0),
+                power_term(n * (n - 1.0), self.v, n - 2.0),
+            )
+        } else {
+            (e * self.ln()).exp()
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Dual: A value and its gradient.
+//
+
+#[derive(Clone, Copy, Debug)]
+pub struct Dual {
+    pub v: f64,
+    pub d: [f64; 3],
+}
+
+impl Dual {
+    pub fn constant(v: f64) -> Dual {
+        Dual { v, d: [0.0; 3] }
+    }
+
+    // The independent variable with the given index.
+    pub fn var(v: f64, idx: usize) -> Dual {
+        let mut x = Dual::constant(v);
+        x.d[idx] = 1.0;
+        x
+    }
+
+    // g(self), where g has value g0 and derivative g1 at self.v.
+    fn chain(self, g0: f64, g1: f64) -> Dual {
+        Dual {
+            v: g0,
+            d: self.d.map(|d| g1 * d),
+        }
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, rhs: Dual) -> Dual {
+        Dual {
+            v: self.v + rhs.v,
+            d: [0, 1, 2].map(|i| self.d[i] + rhs.d[i]),
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, rhs: Dual) -> Dual {
+        self + -rhs
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, rhs: Dual) -> Dual {
+        Dual {
+            v: self.v * rhs.v,
+            d: [0, 1, 2].map(|i| self.d[i] * rhs.v + self.v * rhs.d[i]),
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Dual) -> Dual {
+        let inv = rhs.v.recip();
+        self * rhs.chain(inv, -inv * inv)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        self.chain(-self.v, -1.0)
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Dual;
+    fn add(self, rhs: f64) -> Dual {
+        Dual {
+            v: self.v + rhs,
+            ..self
+        }
+    }
+}
+
+impl Sub<f64> for Dual {
+    type Output = Dual;
+    fn sub(self, rhs: f64) -> Dual {
+        self + -rhs
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Dual;
+    fn mul(self, rhs: f64) -> Dual {
+        self.chain(self.v * rhs, rhs)
+    }
+}
+
+impl Div<f64> for Dual {
+    type Output = Dual;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: f64) -> Dual {
+        self * rhs.recip()
+    }
+}
+
+impl Scalar for Dual {
+    fn from_f64(v: f64) -> Self {
+        Dual::constant(v)
+    }
+
+    fn sin(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        self.chain(s, c)
+    }
+
+    fn cos(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        self.chain(c, -s)
+    }
+
+    fn exp(self) -> Self {
+        let e = self.v.exp();
+        self.chain(e, e)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.v.ln(), self.v.recip())
+    }
+
+    fn sqrt(self) -> Self {
+        let s = self.v.sqrt();
+        self.chain(s, 0.5 / s)
+    }
+
+    fn abs(self) -> Self {
+        self.chain(self.v.abs(), self.v.signum())
+    }
+
+    fn powf(self, e: Self) -> Self {
+        if e.d == [0.0; 3] {
+            // Handles negative bases with integer exponents.
+            let n = e.v;
+            self.chain(self.v.powf(n), power_term(n, self.v, n - 1.0))
+        } else {
+            (e * self.ln()).exp()
+        }
+    }
+}
+
+// coeff * v^n, a term in the derivative of a power. It's zero when
+// coeff is, even where v^n is infinite (e.g. the second derivative of
+// x^1 at 0).
+fn power_term(coeff: f64, v: f64, n: f64) -> f64 {
+    if coeff == 0.0 {
+        0.0
+    } else {
+        coeff * v.powf(n)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Helpers to differentiate a function of (x, y, z) at a point.
+//
+
+pub fn gradient(p: &Vec3, f: impl Fn(Dual, Dual, Dual) -> Dual) -> Vec3 {
+    let r = f(Dual::var(p.x, 0), Dual::var(p.y, 1), Dual::var(p.z, 2));
+    Vec3 {
+        x: r.d[0],
+        y: r.d[1],
+        z: r.d[2],
+    }
+}
+
+pub fn hessian(p: &Vec3, f: impl Fn(HyperDual, HyperDual, HyperDual) -> HyperDual) -> Mat3 {
+    let r = f(
+        HyperDual::var(p.x, 0),
+        HyperDual::var(p.y, 1),
+        HyperDual::var(p.z, 2),
+    );
+    Mat3 { m: r.h }
+}
@@ -0,0 +1,384 @@
+//
+// expr.rs: A tiny expression language for user-defined implicit
+// surfaces, so that new shapes can be typed into the UI rather than
+// compiled in.
+//
+// Expressions are over the variables x, y and z, numeric constants,
+// the named constants "pi" and "e", the operators + - * / ^ (with ^
+// binding tightest and associating to the right), the functions
+// sin, cos, exp, sqrt and abs, and any other identifier, which is
+// treated as a named parameter whose value is supplied at evaluation
+// time.
+//
+// Evaluation is generic over `Scalar`, so user-defined surfaces get
+// exact derivatives through automatic differentiation.
+//
+
+use std::fmt;
+
+use crate::dual::Scalar;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Func {
+    Sin,
+    Cos,
+    Exp,
+    Sqrt,
+    Abs,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Func> {
+        match name {
+            "sin" => Some(Func::Sin),
+            "cos" => Some(Func::Cos),
+            "exp" => Some(Func::Exp),
+            "sqrt" => Some(Func::Sqrt),
+            "abs" => Some(Func::Abs),
+            _ => None,
+        }
+    }
+
+    fn apply<S: Scalar>(&self, v: S) -> S {
+        match self {
+            Func::Sin => v.sin(),
+            Func::Cos => v.cos(),
+            Func::Exp => v.exp(),
+            Func::Sqrt => v.sqrt(),
+            Func::Abs => v.abs(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Const(f64),
+    X,
+    Y,
+    Z,
+    // Index into the expression's parameter list.
+    Param(usize),
+    Neg(Box<Node>),
+    Add(Box<Node>, Box<Node>),
+    Sub(Box<Node>, Box<Node>),
+    Mul(Box<Node>, Box<Node>),
+    Div(Box<Node>, Box<Node>),
+    Pow(Box<Node>, Box<Node>),
+    Call(Func, Box<Node>),
+}
+
+impl Node {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S, params: &[f64]) -> S {
+        let ev = |n: &Node| n.eval(x, y, z, params);
+        match self {
+            Node::Const(c) => S::from_f64(*c),
+            Node::X => x,
+            Node::Y => y,
+            Node::Z => z,
+            // Missing parameters are treated as zero rather than
+            // panicking.
+            Node::Param(idx) => S::from_f64(params.get(*idx).copied().unwrap_or(0.0)),
+            Node::Neg(a) => -ev(a),
+            Node::Add(a, b) => ev(a) + ev(b),
+            Node::Sub(a, b) => ev(a) - ev(b),
+            Node::Mul(a, b) => ev(a) * ev(b),
+            Node::Div(a, b) => ev(a) / ev(b),
+            Node::Pow(a, b) => ev(a).powf(ev(b)),
+            Node::Call(f, a) => f.apply(ev(a)),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Errors
+//
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    // Byte offset into the source where the problem was found.
+    pub pos: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.pos + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+////////////////////////////////////////////////////////////////////////
+// Tokeniser
+//
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    End,
+}
+
+fn tokenise(src: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = pos;
+            while let Some(&(i, c)) = chars.peek() {
+                // Allow exponents such as "1e-3".
+                let is_exp_sign =
+                    (c == '-' || c == '+') && src[..i].ends_with(['e', 'E']) && i > pos;
+                if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_exp_sign {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let text = &src[pos..end];
+            let num = text.parse::<f64>().map_err(|_| ParseError {
+                pos,
+                message: format!("Bad number '{}'", text),
+            })?;
+            tokens.push((pos, Token::Num(num)));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = pos;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push((pos, Token::Ident(src[pos..end].to_string())));
+        } else {
+            let tok = match c {
+                '+' | '-' | '*' | '/' | '^' => Token::Op(c),
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                _ => {
+                    return Err(ParseError {
+                        pos,
+                        message: format!("Unexpected character '{}'", c),
+                    })
+                }
+            };
+            tokens.push((pos, tok));
+            chars.next();
+        }
+    }
+    tokens.push((src.len(), Token::End));
+    Ok(tokens)
+}
+
+////////////////////////////////////////////////////////////////////////
+// Parser: Simple recursive descent.
+//
+// expr  := term (('+' | '-') term)*
+// term  := unary (('*' | '/') unary)*
+// unary := '-' unary | power
+// power := atom ('^' unary)?
+// atom  := number | ident | ident '(' expr ')' | '(' expr ')'
+//
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    idx: usize,
+    params: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.idx].1
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens[self.idx].0
+    }
+
+    fn next(&mut self) -> Token {
+        let tok = self.tokens[self.idx].1.clone();
+        if tok != Token::End {
+            self.idx += 1;
+        }
+        tok
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(ParseError {
+            pos: self.pos(),
+            message: message.to_string(),
+        })
+    }
+
+    fn expect(&mut self, tok: Token, message: &str) -> Result<(), ParseError> {
+        if *self.peek() == tok {
+            self.next();
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    fn expr(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            match self.peek() {
+                Token::Op('+') => {
+                    self.next();
+                    lhs = Node::Add(Box::new(lhs), Box::new(self.term()?));
+                }
+                Token::Op('-') => {
+                    self.next();
+                    lhs = Node::Sub(Box::new(lhs), Box::new(self.term()?));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            match self.peek() {
+                Token::Op('*') => {
+                    self.next();
+                    lhs = Node::Mul(Box::new(lhs), Box::new(self.unary()?));
+                }
+                Token::Op('/') => {
+                    self.next();
+                    lhs = Node::Div(Box::new(lhs), Box::new(self.unary()?));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        if *self.peek() == Token::Op('-') {
+            self.next();
+            Ok(Node::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, ParseError> {
+        let base = self.atom()?;
+        if *self.peek() == Token::Op('^') {
+            self.next();
+            // Right-associative, and "x^-1" is allowed.
+            Ok(Node::Pow(Box::new(base), Box::new(self.unary()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        let pos = self.pos();
+        match self.next() {
+            Token::Num(n) => Ok(Node::Const(n)),
+            Token::LParen => {
+                let inner = self.expr()?;
+                self.expect(Token::RParen, "Expected ')'")?;
+                Ok(inner)
+            }
+            Token::Ident(name) => {
+                if let Some(func) = Func::from_name(&name) {
+                    self.expect(Token::LParen, "Expected '(' after function name")?;
+                    let arg = self.expr()?;
+                    self.expect(Token::RParen, "Expected ')'")?;
+                    return Ok(Node::Call(func, Box::new(arg)));
+                }
+                if *self.peek() == Token::LParen {
+                    return Err(ParseError {
+                        pos,
+                        message: format!("Unknown function '{}'", name),
+                    });
+                }
+                Ok(match name.as_str() {
+                    "x" => Node::X,
+                    "y" => Node::Y,
+                    "z" => Node::Z,
+                    "pi" => Node::Const(std::f64::consts::PI),
+                    "e" => Node::Const(std::f64::consts::E),
+                    _ => {
+                        let idx = match self.params.iter().position(|p| *p == name) {
+                            Some(idx) => idx,
+                            None => {
+                                self.params.push(name);
+                                self.params.len() - 1
+                            }
+                        };
+                        Node::Param(idx)
+                    }
+                })
+            }
+            Token::End => Err(ParseError {
+                pos,
+                message: "Unexpected end of expression".to_string(),
+            }),
+            tok => Err(ParseError {
+                pos,
+                message: format!("Unexpected {}", describe(&tok)),
+            }),
+        }
+    }
+}
+
+fn describe(tok: &Token) -> String {
+    match tok {
+        Token::Num(n) => format!("number {}", n),
+        Token::Ident(s) => format!("'{}'", s),
+        Token::Op(c) => format!("'{}'", c),
+        Token::LParen => "'('".to_string(),
+        Token::RParen => "')'".to_string(),
+        Token::End => "end of expression".to_string(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Expression: The public face of all this.
+//
+
+#[derive(Clone, Debug)]
+pub struct Expression {
+    root: Node,
+    params: Vec<String>,
+}
+
+impl Expression {
+    pub fn parse(src: &str) -> Result<Expression, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenise(src)?,
+            idx: 0,
+            params: Vec::new(),
+        };
+        let root = parser.expr()?;
+        if *parser.peek() != Token::End {
+            let tok = parser.peek().clone();
+            return parser.error(&format!("Unexpected {}", describe(&tok)));
+        }
+        Ok(Expression {
+            root,
+            params: parser.params,
+        })
+    }
+
+    // Names of the free parameters, in order of first appearance. The
+    // values passed to `eval` are in the same order.
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn eval<S: Scalar>(&self, x: S, y: S, z: S, params: &[f64]) -> S {
+        self.root.eval(x, y, z, params)
+    }
+}
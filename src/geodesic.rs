@@ -0,0 +1,222 @@
+//
+// geodesic.rs: Integrating the geodesic equation on an implicit
+// surface f(p) = 0, as an alternative to the extrapolate-and-project
+// stepper.
+//
+// A curve p(s) parameterised by arc length stays on the surface while
+// grad f . v = 0, where v = p'. Differentiating that along the curve,
+// and requiring the acceleration to be purely normal to the surface
+// (i.e. no sideways curving within the surface), gives
+//
+//   p'' = -(v^T H v / |grad f|^2) grad f
+//
+// where H is the Hessian of f. This is a second-order ODE, which we
+// integrate as a first-order system in (p, v). Nothing here depends on
+// the number of dimensions, so hyper.rs uses it for 3D spaces in 4D.
+//
+
+use crate::implicit::{self, Implicit};
+use crate::mat3::*;
+use crate::vec3::*;
+use crate::vector::Vector;
+
+// Supplies the derivatives of the implicit function.
+pub trait Field {
+    fn gradient(&self, p: &Vec3) -> Vec3;
+    fn hessian(&self, p: &Vec3) -> Mat3;
+}
+
+// A second-order ODE p'' = a(p, p'), which the integrators below can
+// solve. Geodesics on implicit surfaces are one; metric.rs supplies
+// another.
+pub trait Equation<V: Vector> {
+    fn acceleration(&self, p: &V, v: &V) -> V;
+}
+
+impl<F: Field> Equation<Vec3> for F {
+    fn acceleration(&self, p: &Vec3, v: &Vec3) -> Vec3 {
+        acceleration(self, p, v)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct State<V: Vector> {
+    // Position.
+    pub p: V,
+    // Unit tangent (velocity with respect to arc length).
+    pub v: V,
+}
+
+// Derivative of the state: (velocity, acceleration).
+type Deriv<V> = (V, V);
+
+pub fn acceleration(field: &impl Field, p: &Vec3, v: &Vec3) -> Vec3 {
+    let grad = field.gradient(p);
+    let curvature = field.hessian(p).quad_form(v) / grad.dot(&grad);
+    grad.scale(-curvature)
+}
+
+// The same, for an implicit surface in any number of dimensions, with
+// the second derivative along v from the surface if it knows it, or
+// differences of its gradient otherwise.
+pub fn implicit_acceleration<V: Vector>(f: &(impl Implicit<V> + ?Sized), p: &V, v: &V) -> V {
+    let grad = f.gradient(p);
+    let second = f
+        .second_along(p, v)
+        .unwrap_or_else(|| implicit::central_second_along(f, p, v));
+    grad.scale(-second / grad.dot(&grad))
+}
+
+fn deriv<V: Vector>(eqn: &(impl Equation<V> + ?Sized), s: &State<V>) -> Deriv<V> {
+    (s.v.clone(), eqn.acceleration(&s.p, &s.v))
+}
+
+// h * sum(coeffs[i] * ks[i]).
+fn weighted_sum<V: Vector>(ks: &[Deriv<V>], coeffs: &[f64], h: f64) -> Deriv<V> {
+    let mut dp = V::zero();
+    let mut dv = V::zero();
+    for (k, c) in ks.iter().zip(coeffs.iter()) {
+        if *c != 0.0 {
+            dp = dp.add(&k.0.scale(h * c));
+            dv = dv.add(&k.1.scale(h * c));
+        }
+    }
+    (dp, dv)
+}
+
+// s + h * sum(coeffs[i] * ks[i]).
+fn combine<V: Vector>(s: &State<V>, ks: &[Deriv<V>], coeffs: &[f64], h: f64) -> State<V> {
+    let (dp, dv) = weighted_sum(ks, coeffs, h);
+    State {
+        p: s.p.add(&dp),
+        v: s.v.add(&dv),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Classic fixed-step fourth-order Runge-Kutta.
+//
+
+pub fn rk4_step<V: Vector>(eqn: &(impl Equation<V> + ?Sized), s: &State<V>, h: f64) -> State<V> {
+    let k1 = deriv(eqn, s);
+    let k2 = deriv(eqn, &combine(s, std::slice::from_ref(&k1), &[0.5], h));
+    let k3 = deriv(eqn, &combine(s, std::slice::from_ref(&k2), &[0.5], h));
+    let k4 = deriv(eqn, &combine(s, std::slice::from_ref(&k3), &[1.0], h));
+    combine(
+        s,
+        &[k1, k2, k3, k4],
+        &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+        h,
+    )
+}
+
+////////////////////////////////////////////////////////////////////////
+// Adaptive Dormand-Prince 5(4): A fifth-order step, with the
+// difference from an embedded fourth-order step used as an error
+// estimate to control the step size.
+//
+
+const DP_A: [&[f64]; 6] = [
+    &[1.0 / 5.0],
+    &[3.0 / 40.0, 9.0 / 40.0],
+    &[44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+    &[
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+    ],
+    &[
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+    ],
+    &[
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+
+// Fifth-order weights minus fourth-order weights.
+const DP_E: [f64; 7] = [
+    71.0 / 57600.0,
+    0.0,
+    -71.0 / 16695.0,
+    71.0 / 1920.0,
+    -17253.0 / 339200.0,
+    22.0 / 525.0,
+    -1.0 / 40.0,
+];
+
+// Returns the fifth-order result and the estimated error.
+fn dopri_step<V: Vector>(
+    eqn: &(impl Equation<V> + ?Sized),
+    s: &State<V>,
+    h: f64,
+) -> (State<V>, f64) {
+    let mut ks = vec![deriv(eqn, s)];
+    for a in DP_A.iter() {
+        ks.push(deriv(eqn, &combine(s, &ks, a, h)));
+    }
+    // The last row of A is also the fifth-order weights.
+    let next = combine(s, &ks, DP_A[5], h);
+    let (err_p, err_v) = weighted_sum(&ks, &DP_E, h);
+    (next, err_p.len().max(err_v.len()))
+}
+
+pub struct Adaptive {
+    // Step size to try next.
+    h: f64,
+    h_min: f64,
+    h_max: f64,
+    // Maximum error allowed per step.
+    tol: f64,
+}
+
+impl Adaptive {
+    pub fn new(h: f64, h_max: f64, tol: f64) -> Adaptive {
+        Adaptive {
+            h,
+            h_min: h * 1.0e-4,
+            h_max,
+            tol,
+        }
+    }
+
+    // Take a step, shrinking it until the error is within tolerance,
+    // and choosing the size of the next one. Returns None if the
+    // step size collapses or the solution blows up.
+    pub fn step<V: Vector>(
+        &mut self,
+        eqn: &(impl Equation<V> + ?Sized),
+        s: &State<V>,
+    ) -> Option<State<V>> {
+        loop {
+            let (next, err) = dopri_step(eqn, s, self.h);
+            if !err.is_finite() {
+                return None;
+            }
+            // Standard controller, with a safety factor and limits on
+            // how fast the step size changes.
+            let factor = if err > 0.0 {
+                (0.9 * (self.tol / err).powf(0.2)).clamp(0.2, 5.0)
+            } else {
+                5.0
+            };
+            let h = self.h;
+            self.h = (h * factor).clamp(self.h_min, self.h_max);
+            if err <= self.tol {
+                return Some(next);
+            }
+            if h <= self.h_min {
+                return None;
+            }
+        }
+    }
+}
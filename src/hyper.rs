@@ -0,0 +1,332 @@
+//
+// hyper.rs: Tracing through a curved 3D space, given as a hypersurface
+// f(x, y, z, w) = 0 in R^4.
+//
+// The stepping is stepper.rs's, shared with the 2D tracer, so rays
+// here are traced with whichever stepper is chosen for the surface.
+// Rays leave a point in a cone of directions in x/y/z, and the paths
+// are drawn by dropping w.
+//
+// HyperView holds the controls and drawing for this, so the tracer
+// only has to say when to redraw.
+//
+
+use glow::Context;
+
+use crate::colour;
+use crate::geodesic::{self, Equation, State};
+use crate::implicit::{self, Implicit};
+use crate::shape::Shape;
+use crate::stepper::{self, Stepper};
+use crate::vec3::*;
+use crate::vec4::*;
+use crate::vector::Vector;
+
+////////////////////////////////////////////////////////////////////////
+// The Hypersurface trait.
+//
+
+pub trait Hypersurface {
+    // Name shown in the UI.
+    fn label(&self) -> &str;
+
+    // The implicit function, zero on the hypersurface.
+    fn value(&self, p: &Vec4) -> f64;
+
+    // Rays are traced within the cube from -extent to extent in x, y
+    // and z.
+    fn extent(&self) -> f64 {
+        1.0
+    }
+}
+
+// Normals come from central differences.
+impl Implicit<Vec4> for dyn Hypersurface + '_ {
+    fn value(&self, p: &Vec4) -> f64 {
+        Hypersurface::value(self, p)
+    }
+}
+
+impl Equation<Vec4> for dyn Hypersurface + '_ {
+    fn acceleration(&self, p: &Vec4, v: &Vec4) -> Vec4 {
+        geodesic::implicit_acceleration(self, p, v)
+    }
+}
+
+// The hypersurfaces that come with the program, in the order they
+// appear in the UI.
+pub fn builtin_hypersurfaces() -> Vec<Box<dyn Hypersurface>> {
+    vec![Box::new(ThreeSphere), Box::new(Bump), Box::new(Wormhole)]
+}
+
+// The unit 3-sphere: Closed, with constant positive curvature, so
+// rays from a point all meet again at the antipode.
+pub struct ThreeSphere;
+
+impl Hypersurface for ThreeSphere {
+    fn label(&self) -> &str {
+        "3-sphere"
+    }
+
+    fn value(&self, p: &Vec4) -> f64 {
+        p.dot(p) - 1.0
+    }
+}
+
+// Flat space with a lump in it, rising in w around the origin.
+pub struct Bump;
+
+impl Hypersurface for Bump {
+    fn label(&self) -> &str {
+        "Bump"
+    }
+
+    fn value(&self, p: &Vec4) -> f64 {
+        0.5 * (-4.0 * p.xyz().dot(&p.xyz())).exp() - p.w
+    }
+}
+
+// The 3D version of the wormhole surface: Two flat-ish spaces,
+// w > 0 and w < 0, joined by a spherical throat.
+pub struct Wormhole;
+
+impl Hypersurface for Wormhole {
+    fn label(&self) -> &str {
+        "Wormhole"
+    }
+
+    fn value(&self, p: &Vec4) -> f64 {
+        p.xyz().dot(&p.xyz()) - p.w * p.w - 0.1
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Starting points and directions.
+//
+
+// The point on the hypersurface above or below (x, y, z), found by
+// searching along w from w = 1.
+pub fn lift(surface: &dyn Hypersurface, p: &Vec3) -> Option<Vec4> {
+    const W_AXIS: Vec4 = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+    let start = Vec4 {
+        x: p.x,
+        y: p.y,
+        z: p.z,
+        w: 1.0,
+    };
+    implicit::intersect_line(surface, &start, &W_AXIS)
+}
+
+// A cone of directions around axis, with the given half-angle (in
+// radians): The axis itself, then count directions evenly spaced
+// around the cone.
+pub fn cone(axis: &Vec3, half_angle: f64, count: usize) -> Vec<Vec3> {
+    let axis = axis.norm();
+    // Any vector not parallel to the axis gives a perpendicular pair.
+    let other = if axis.x.abs() < 0.9 {
+        Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    } else {
+        Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    };
+    let e1 = axis.cross(&other).norm();
+    let e2 = axis.cross(&e1);
+
+    let mut dirs = vec![axis.clone()];
+    if half_angle > 0.0 {
+        for idx in 0..count {
+            let phi = 2.0 * std::f64::consts::PI * idx as f64 / count as f64;
+            let around = e1.scale(phi.cos()).add(&e2.scale(phi.sin()));
+            dirs.push(
+                axis.scale(half_angle.cos())
+                    .add(&around.scale(half_angle.sin())),
+            );
+        }
+    }
+    dirs
+}
+
+////////////////////////////////////////////////////////////////////////
+// The view: The controls for a cone of rays, and the paths they trace.
+//
+
+// Size of a step along a ray, and the most points in one, as for the
+// 2D tracer.
+const RAY_STEP: f64 = 0.01;
+const MAX_PATH_POINTS: usize = 10_000;
+
+// Largest step the adaptive integrator may take, to keep the drawn
+// paths smooth.
+const MAX_ADAPTIVE_STEP: f64 = 0.05;
+
+// Smallest w to scale the colours by, so flat paths don't divide by
+// zero.
+const MIN_W_SCALE: f64 = 1.0e-7;
+
+pub struct HyperView {
+    // The paths, projected into 3D by dropping w, and coloured by w.
+    pub paths: Shape,
+    show: bool,
+    surfaces: Vec<Box<dyn Hypersurface>>,
+    idx: usize,
+    // Where the rays start, in x/y/z.
+    start: (f64, f64, f64),
+    // The direction of the cone of rays' axis, as azimuth and
+    // elevation, and its half-angle, all in degrees.
+    dir: (f64, f64),
+    cone: f64,
+    rays: usize,
+}
+
+impl HyperView {
+    pub fn new(gl: &Context) -> HyperView {
+        HyperView {
+            paths: Shape::new_colored(gl),
+            show: false,
+            surfaces: builtin_hypersurfaces(),
+            idx: 0,
+            start: (0.0, -0.5, 0.0),
+            dir: (0.0, 0.0),
+            cone: 30.0,
+            rays: 12,
+        }
+    }
+
+    // The controls. Returns whether the rays need tracing again.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.show, "Show").changed();
+        changed |= egui::ComboBox::from_label("Hypersurface")
+            .selected_text(self.surfaces[self.idx].label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for (idx, surface) in self.surfaces.iter().enumerate() {
+                    changed |= ui
+                        .selectable_value(&mut self.idx, idx, surface.label())
+                        .changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false);
+        let extent = self.surfaces[self.idx].extent();
+        let (x, y, z) = &mut self.start;
+        for (name, coord) in [("X start", x), ("Y start", y), ("Z start", z)] {
+            changed |= ui
+                .add(egui::Slider::new(coord, -extent..=extent).text(name))
+                .changed();
+        }
+        changed |= ui
+            .add(egui::Slider::new(&mut self.dir.0, -180.0..=180.0).text("Azimuth"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.dir.1, -90.0..=90.0).text("Elevation"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.cone, 0.0..=180.0).text("Cone angle"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.rays, 1..=60).text("Cone rays"))
+            .changed();
+        changed
+    }
+
+    // Trace the cone of rays, up to max_len long, with the given
+    // stepper and integrator settings, and draw them by dropping w,
+    // coloured by w instead.
+    pub fn rebuild(
+        &mut self,
+        gl: &Context,
+        stepper: Stepper,
+        reproject: bool,
+        tolerance: f64,
+        max_len: f64,
+    ) {
+        let surface = self.surfaces[self.idx].as_ref();
+        let (x, y, z) = self.start;
+        let start = lift(surface, &Vec3 { x, y, z }).filter(|_| self.show);
+        let Some(start) = start else {
+            self.paths.rebuild(gl, &[], &[]);
+            return;
+        };
+
+        let (azimuth, elevation) = (self.dir.0.to_radians(), self.dir.1.to_radians());
+        let axis = Vec3 {
+            x: azimuth.sin() * elevation.cos(),
+            y: azimuth.cos() * elevation.cos(),
+            z: elevation.sin(),
+        };
+        let extent = surface.extent();
+        let inside = |p: &Vec4| p.x.abs() <= extent && p.y.abs() <= extent && p.z.abs() <= extent;
+        let paths = cone(&axis, self.cone.to_radians(), self.rays)
+            .iter()
+            .map(|dir| {
+                let dir = Vec4 {
+                    x: dir.x,
+                    y: dir.y,
+                    z: dir.z,
+                    w: 0.0,
+                };
+                let mut state = State {
+                    p: start.clone(),
+                    v: stepper::tangent_part(surface, &start, &dir).norm(),
+                };
+                let mut adaptive = geodesic::Adaptive::new(RAY_STEP, MAX_ADAPTIVE_STEP, tolerance);
+                let mut points = vec![state.p.clone()];
+                let mut len = 0.0;
+                while len < max_len && points.len() < MAX_PATH_POINTS && inside(&state.p) {
+                    let Some(next) = stepper::advance(
+                        surface,
+                        stepper,
+                        reproject,
+                        &state,
+                        RAY_STEP,
+                        &mut adaptive,
+                    ) else {
+                        break;
+                    };
+                    len += next.p.sub(&state.p).len();
+                    state = next;
+                    points.push(state.p.clone());
+                }
+                points
+            })
+            .collect::<Vec<_>>();
+
+        let max_w = paths
+            .iter()
+            .flatten()
+            .map(|p| p.w.abs())
+            .fold(0.0, f64::max)
+            .max(MIN_W_SCALE);
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for path in paths.iter() {
+            let start = vertices.len() / 6;
+            for p in path.iter() {
+                p.xyz().push_to(&mut vertices);
+                vertices.extend(colour::diverging(p.w / max_w));
+            }
+            let end = vertices.len() / 6;
+            for idx in start..end.saturating_sub(1) {
+                indices.extend([idx as u32, idx as u32 + 1]);
+            }
+        }
+        self.paths.rebuild(gl, &vertices, &indices);
+    }
+
+    pub fn close(&self, gl: &Context) {
+        self.paths.close(gl);
+    }
+}
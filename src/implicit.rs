@@ -0,0 +1,141 @@
+//
+// implicit.rs: Moving around on implicit surfaces f(p) = 0, in any
+// number of dimensions: Meeting lines with the surface, and taking
+// steps constrained to it. The tracer uses this for surfaces in 3D,
+// and hyper.rs for 3D spaces in 4D.
+//
+
+use crate::vector::Vector;
+
+// Convergence tolerance for the solver.
+const EPSILON: f64 = 1.0e-7;
+
+// Step size for the central differences giving the gradient.
+const GRAD_EPSILON: f64 = 1.0e-5;
+
+// Step size for the second derivative, which takes differences of the
+// gradient, so needs a larger step to keep rounding error down.
+const SECOND_EPSILON: f64 = 1.0e-4;
+
+pub trait Implicit<V: Vector> {
+    // The implicit function, zero on the surface.
+    fn value(&self, p: &V) -> f64;
+
+    // The gradient of the function, i.e. a (not normalised) normal.
+    fn gradient(&self, p: &V) -> V {
+        central_gradient(|q| self.value(q), p)
+    }
+
+    // The second derivative of the function along direction d, if
+    // known, which lets intersect_line use Halley's method.
+    fn second_along(&self, _p: &V, _d: &V) -> Option<f64> {
+        None
+    }
+}
+
+// The gradient of f at p, by central differences.
+pub fn central_gradient<V: Vector>(f: impl Fn(&V) -> f64, p: &V) -> V {
+    let mut grad = V::zero();
+    for idx in 0..V::DIM {
+        let dp = V::axis(idx).scale(GRAD_EPSILON);
+        let diff = (f(&p.add(&dp)) - f(&p.sub(&dp))) / (2.0 * GRAD_EPSILON);
+        grad = grad.add(&V::axis(idx).scale(diff));
+    }
+    grad
+}
+
+// The second derivative of f along d at p, by central differences of
+// the gradient.
+pub fn central_second_along<V: Vector>(f: &(impl Implicit<V> + ?Sized), p: &V, d: &V) -> f64 {
+    let dp = d.scale(SECOND_EPSILON);
+    let diff = f.gradient(&p.add(&dp)).sub(&f.gradient(&p.sub(&dp)));
+    diff.dot(d) / (2.0 * SECOND_EPSILON)
+}
+
+// Where the line through point in the given direction meets the
+// surface, nearest to point.
+pub fn intersect_line<V: Vector>(
+    f: &(impl Implicit<V> + ?Sized),
+    point: &V,
+    direction: &V,
+) -> Option<V> {
+    // Halley's method on f(point + lambda direction), where the second
+    // derivative along the line is known, and Newton-Raphson
+    // otherwise. Halley's converges faster and more robustly near
+    // tight curvature such as the wormhole's throat.
+    //
+    // In practice, it's locally flat enough that a a single
+    // iteration seems to suffice.
+    const MAX_ITER: usize = 10;
+
+    let mut lambda = 0.0;
+    for _ in 0..MAX_ITER {
+        let guess = point.add(&direction.scale(lambda));
+        let guess_val = f.value(&guess);
+        if guess_val.abs() < EPSILON {
+            return Some(guess);
+        }
+
+        let d1 = f.gradient(&guess).dot(direction);
+        // Fall back to a Newton step if the Halley correction would
+        // flip the step's direction.
+        let halley = f.second_along(&guess, direction).and_then(|d2| {
+            let denom = 2.0 * d1 * d1 - guess_val * d2;
+            (denom > d1 * d1 * 0.5).then(|| 2.0 * guess_val * d1 / denom)
+        });
+        lambda -= halley.unwrap_or(guess_val / d1);
+    }
+
+    // Could fall back to binary chop, but as it generally seems to
+    // converge in <= 2 iterations if there is a solution, this seems
+    // excessive.
+    None
+}
+
+// Take a step from p by delta, constrained to the surface along norm.
+pub fn step<V: Vector>(f: &(impl Implicit<V> + ?Sized), p: &V, delta: &V, norm: &V) -> Option<V> {
+    // If curvature is extreme, there may be no intersection, because
+    // the normal at p and the normal at the intersection point are
+    // sufficiently different. We try again with a smaller step.
+    //
+    // An example of extreme curvature is the "wormhole" surface with
+    // z_scale around e.g. 0.01.
+    const MAX_ITER: usize = 8;
+
+    let mut delta = delta.clone();
+    for _ in 0..MAX_ITER {
+        if let Some(new_p) = intersect_line(f, &p.add(&delta), norm) {
+            return Some(new_p);
+        }
+        delta = delta.scale(0.5);
+    }
+    None
+}
+
+// Take a step from p by delta, then move to the nearest point on the
+// surface. That point is where the normal passes back through the
+// extrapolated point, which we find by repeatedly projecting along the
+// normal at the latest estimate.
+pub fn step_nearest<V: Vector>(f: &(impl Implicit<V> + ?Sized), p: &V, delta: &V) -> Option<V> {
+    let guess = step(f, p, delta, &f.gradient(p).norm())?;
+    refine_nearest(f, &p.add(delta), guess)
+}
+
+// Improve a guess at the nearest point on the surface to target.
+pub fn refine_nearest<V: Vector>(
+    f: &(impl Implicit<V> + ?Sized),
+    target: &V,
+    mut guess: V,
+) -> Option<V> {
+    const MAX_ITER: usize = 10;
+    for _ in 0..MAX_ITER {
+        let norm = f.gradient(&guess).norm();
+        let new_guess = intersect_line(f, target, &norm)?;
+        let moved = new_guess.sub(&guess).len();
+        guess = new_guess;
+        if moved < EPSILON {
+            break;
+        }
+    }
+    Some(guess)
+}
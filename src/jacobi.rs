@@ -0,0 +1,60 @@
+//
+// jacobi.rs: Jacobi fields, describing how nearby geodesics deviate
+// from one another.
+//
+// For geodesics leaving a point at angles differing by a small d
+// theta, their separation at arc length s is J(s) d theta, where J
+// solves the Jacobi equation
+//
+//   J'' + K(s) J = 0,   J(0) = 0,   J'(0) = 1
+//
+// and K(s) is the Gaussian curvature along the geodesic. Positive
+// curvature focuses geodesics (J grows more slowly than s, and may
+// come back to zero at a conjugate point), negative curvature makes
+// them diverge faster.
+//
+
+// Integrate the Jacobi equation along a geodesic, given the arc
+// length and curvature at each point. K is interpolated linearly
+// between points, and each segment is taken as a single RK4 step.
+// Returns J at each point.
+pub fn integrate(lens: &[f64], ks: &[f64]) -> Vec<f64> {
+    let mut result = Vec::with_capacity(lens.len());
+    if lens.is_empty() {
+        return result;
+    }
+    // (J, J').
+    let mut state = (0.0, 1.0);
+    result.push(state.0);
+    for idx in 1..lens.len() {
+        let h = lens[idx] - lens[idx - 1];
+        let (k0, k1) = (ks[idx - 1], ks[idx]);
+        let deriv = |t: f64, (j, dj): (f64, f64)| (dj, -(k0 + (k1 - k0) * t) * j);
+        let step =
+            |(j, dj): (f64, f64), (a, b): (f64, f64), f: f64| (j + a * h * f, dj + b * h * f);
+
+        let d1 = deriv(0.0, state);
+        let d2 = deriv(0.5, step(state, d1, 0.5));
+        let d3 = deriv(0.5, step(state, d2, 0.5));
+        let d4 = deriv(1.0, step(state, d3, 1.0));
+        state = (
+            state.0 + h / 6.0 * (d1.0 + 2.0 * d2.0 + 2.0 * d3.0 + d4.0),
+            state.1 + h / 6.0 * (d1.1 + 2.0 * d2.1 + 2.0 * d3.1 + d4.1),
+        );
+        result.push(state.0);
+    }
+    result
+}
+
+// The arc lengths of the conjugate points, where J returns to zero,
+// interpolated between points.
+pub fn conjugate_points(lens: &[f64], js: &[f64]) -> Vec<f64> {
+    // Skip the start, where J is zero by definition.
+    (2..js.len())
+        .filter_map(|idx| {
+            let (j0, j1) = (js[idx - 1], js[idx]);
+            (j0 != 0.0 && (j0 > 0.0) != (j1 > 0.0))
+                .then(|| lens[idx - 1] + (lens[idx] - lens[idx - 1]) * j0 / (j0 - j1))
+        })
+        .collect()
+}
@@ -0,0 +1,38 @@
+//
+// lib.rs: Everything but the window. The binary (main.rs) wraps the
+// tracer and camera in a window and an event loop, while the rest can
+// be used headless, such as the wormhole raytracer in
+// examples/render_wormhole.rs.
+//
+
+pub mod bvp;
+pub mod camera;
+pub mod colour;
+pub mod curvature;
+pub mod cutlocus;
+pub mod dual;
+pub mod expr;
+pub mod fmm;
+pub mod geodesic;
+pub mod hyper;
+pub mod implicit;
+pub mod jacobi;
+pub mod mat3;
+pub mod mat4;
+pub mod mesh;
+pub mod mesher;
+pub mod metric;
+pub mod objects;
+pub mod observer;
+pub mod plot;
+pub mod polygon;
+pub mod polyline;
+pub mod shape;
+pub mod stepper;
+pub mod surface;
+pub mod tracer;
+pub mod transport;
+pub mod vec3;
+pub mod vec4;
+pub mod vector;
+pub mod wormhole;
@@ -8,15 +8,23 @@
 use anyhow::*;
 use glow::{Context, *};
 
-mod tracer;
-mod vec3;
-
-use crate::tracer::*;
+use curved_space::camera::Camera;
+use curved_space::tracer::*;
 
 ////////////////////////////////////////////////////////////////////////
 // winit: Shared between wasm32 and glutin_winit.
 //
 
+// Camera movement per step of the mouse wheel (as a log of the zoom
+// factor), and per press of the arrow keys (in degrees). Wheels that
+// scroll by pixels get a line per PIXELS_PER_LINE.
+#[cfg(any(target_arch = "wasm32", feature = "glutin_winit"))]
+const ZOOM_PER_LINE: f64 = 0.1;
+#[cfg(any(target_arch = "wasm32", feature = "glutin_winit"))]
+const ORBIT_STEP: f64 = 5.0;
+#[cfg(any(target_arch = "wasm32", feature = "glutin_winit"))]
+const PIXELS_PER_LINE: f64 = 50.0;
+
 #[cfg(any(target_arch = "wasm32", feature = "glutin_winit"))]
 #[derive(Debug)]
 pub enum UserEvent {
@@ -61,6 +69,13 @@ impl Platform {
 
         let mut repaint_delay = std::time::Duration::MAX;
         let mut left_button_down = false;
+        let mut right_button_down = false;
+        // Shift-click picks a point on the surface: Whatever the UI is
+        // waiting to place, or else the ray origin, after which
+        // dragging aims the fan.
+        let mut shift_down = false;
+        let mut aiming = false;
+        let mut cursor = None;
 
         let event_fn =
             move |event,
@@ -136,10 +151,43 @@ impl Platform {
                                 // than the DeviceEvent in order to
                                 // allow egui to consume it first.
                                 WindowEvent::MouseInput { state, button, .. } => {
-                                    if button == MouseButton::Left {
-                                        left_button_down = state == ElementState::Pressed;
+                                    let pressed = state == ElementState::Pressed;
+                                    match button {
+                                        MouseButton::Left if pressed && shift_down => {
+                                            let picked = self.pick(&drawable, cursor);
+                                            if let Some(p) = picked {
+                                                aiming = drawable.tracer.place(&self.gl, p);
+                                                self.window.request_redraw();
+                                            }
+                                        }
+                                        MouseButton::Left => {
+                                            left_button_down = pressed;
+                                            aiming = false;
+                                        }
+                                        MouseButton::Right => right_button_down = pressed,
+                                        _ => {}
+                                    }
+                                }
+                                WindowEvent::CursorMoved { position, .. } => {
+                                    cursor = Some(position);
+                                    if aiming {
+                                        if let Some(p) = self.pick(&drawable, cursor) {
+                                            drawable.tracer.aim(&self.gl, p);
+                                            self.window.request_redraw();
+                                        }
                                     }
                                 }
+                                WindowEvent::ModifiersChanged(modifiers) => {
+                                    shift_down = modifiers.state().shift_key();
+                                }
+                                WindowEvent::MouseWheel { delta, .. } => {
+                                    let lines = match delta {
+                                        MouseScrollDelta::LineDelta(_, y) => y as f64,
+                                        MouseScrollDelta::PixelDelta(p) => p.y / PIXELS_PER_LINE,
+                                    };
+                                    drawable.camera.zoom((lines * ZOOM_PER_LINE).exp());
+                                    self.window.request_redraw();
+                                }
                                 // We will make use of keyboard
                                 // auto-repeat for movement, rather
                                 // than doing our own key-held
@@ -154,6 +202,7 @@ impl Platform {
                                     } = event
                                     {
                                         let t = &mut drawable.tracer;
+                                        let camera = &mut drawable.camera;
                                         match k {
                                             KeyCode::KeyW => {
                                                 t.update_origin(&self.gl, 0.0, 0.01, 0.0)
@@ -173,8 +222,20 @@ impl Platform {
                                             KeyCode::KeyE => {
                                                 t.update_origin(&self.gl, 0.0, 0.0, 1.0)
                                             }
-                                            _ => {}
+                                            KeyCode::ArrowLeft => camera.orbit(-ORBIT_STEP, 0.0),
+                                            KeyCode::ArrowRight => camera.orbit(ORBIT_STEP, 0.0),
+                                            KeyCode::ArrowUp => camera.orbit(0.0, ORBIT_STEP),
+                                            KeyCode::ArrowDown => camera.orbit(0.0, -ORBIT_STEP),
+                                            KeyCode::Equal | KeyCode::NumpadAdd => {
+                                                camera.zoom(ZOOM_PER_LINE.exp())
+                                            }
+                                            KeyCode::Minus | KeyCode::NumpadSubtract => {
+                                                camera.zoom((-ZOOM_PER_LINE).exp())
+                                            }
+                                            KeyCode::KeyR => *camera = Camera::default(),
+                                            _ => return,
                                         }
+                                        self.window.request_redraw();
                                     }
                                 }
                                 _ => {}
@@ -183,24 +244,21 @@ impl Platform {
                     }
 
                     Event::DeviceEvent { event, .. } => {
-                        if left_button_down {
-                            // DeviceEvent is better than WindowEvent for
-                            // this kind of camera dragging, according to
-                            // the docs.
-                            if let DeviceEvent::MouseMotion { delta } = event {
-                                let size = self.window.inner_size();
-                                let x = (delta.0 as f32) * 360.0 / size.width as f32;
-                                let y = (delta.1 as f32) * 180.0 / size.height as f32;
-
-                                let turn = &mut drawable.turn;
-                                let tilt = &mut drawable.tilt;
-                                *turn += x;
-                                if *turn > 180.0 {
-                                    *turn -= 360.0;
-                                } else if *turn < -180.0 {
-                                    *turn += 360.0;
-                                }
-                                *tilt = (*tilt + y).min(90.0).max(-90.0);
+                        // DeviceEvent is better than WindowEvent for
+                        // this kind of camera dragging, according to
+                        // the docs.
+                        if let DeviceEvent::MouseMotion { delta } = event {
+                            let size = self.window.inner_size();
+                            if left_button_down {
+                                let x = delta.0 * 360.0 / size.width as f64;
+                                let y = delta.1 * 180.0 / size.height as f64;
+                                drawable.camera.orbit(x, y);
+                                self.window.request_redraw();
+                            } else if right_button_down {
+                                drawable
+                                    .camera
+                                    .pan(delta.0, delta.1, size.width, size.height);
+                                self.window.request_redraw();
                             }
                         }
                     }
@@ -222,6 +280,19 @@ impl Platform {
 
         Self::run_event_loop(event_loop, event_fn);
     }
+
+    // The point in X/Y on the surface under the cursor, if any.
+    fn pick(
+        &self,
+        drawable: &Drawable,
+        cursor: Option<winit::dpi::PhysicalPosition<f64>>,
+    ) -> Option<(f64, f64)> {
+        let cursor = cursor?;
+        let size = self.window.inner_size();
+        let camera = &drawable.camera;
+        let (eye, dir) = camera.ray(cursor.x, cursor.y, size.width, size.height);
+        drawable.tracer.pick(&eye, &dir, camera.far)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////
@@ -357,7 +428,7 @@ impl Platform {
         let window_builder = winit::window::WindowBuilder::new()
             .with_title(name)
             .with_inner_size(winit::dpi::LogicalSize::new(width as f32, height as f32));
-        let template = ConfigTemplateBuilder::new();
+        let template = ConfigTemplateBuilder::new().with_depth_size(24);
         let display_builder = DisplayBuilder::new().with_window_builder(Some(window_builder));
         let (window, gl_config) = display_builder
             .build(&event_loop, template, |configs| {
@@ -472,6 +543,7 @@ impl Platform {
         let gl_attr = video.gl_attr();
         gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
         gl_attr.set_context_version(3, 0);
+        gl_attr.set_depth_size(24);
         let window = video
             .window(name, width, height)
             .opengl()
@@ -546,13 +618,12 @@ fn main() -> Result<()> {
 
 struct Drawable {
     program: Program,
-    tilt_id: UniformLocation,
-    tilt: f32,
-    turn_id: UniformLocation,
-    turn: f32,
-    x_scale_id: UniformLocation,
-    y_scale_id: UniformLocation,
+    transform_id: UniformLocation,
+    normal_rotation_id: UniformLocation,
+    camera: Camera,
     color_id: UniformLocation,
+    vertex_color_id: UniformLocation,
+    lit_id: UniformLocation,
     tracer: Tracer,
 }
 
@@ -584,16 +655,20 @@ impl Drawable {
                 shaders.push(shader);
             }
 
+            // Shapes use fixed attribute locations, so pin them down.
+            gl.bind_attrib_location(program, 0, "in_vert");
+            gl.bind_attrib_location(program, 1, "in_color");
+            gl.bind_attrib_location(program, 2, "in_normal");
             gl.link_program(program);
             if !gl.get_program_link_status(program) {
                 panic!("{}", gl.get_program_info_log(program));
             }
 
-            let tilt_id = gl.get_uniform_location(program, "tilt").unwrap();
-            let turn_id = gl.get_uniform_location(program, "turn").unwrap();
-            let x_scale_id = gl.get_uniform_location(program, "x_scale").unwrap();
-            let y_scale_id = gl.get_uniform_location(program, "y_scale").unwrap();
+            let transform_id = gl.get_uniform_location(program, "transform").unwrap();
+            let normal_rotation_id = gl.get_uniform_location(program, "normal_rotation").unwrap();
             let color_id = gl.get_uniform_location(program, "color").unwrap();
+            let vertex_color_id = gl.get_uniform_location(program, "vertex_color").unwrap();
+            let lit_id = gl.get_uniform_location(program, "lit").unwrap();
 
             for shader in shaders {
                 gl.detach_shader(program, shader);
@@ -602,13 +677,12 @@ impl Drawable {
 
             let mut this = Drawable {
                 program,
-                tilt_id,
-                tilt: 30.0f32,
-                turn_id,
-                turn: 0.0f32,
-                x_scale_id,
-                y_scale_id,
+                transform_id,
+                normal_rotation_id,
+                camera: Camera::default(),
                 color_id,
+                vertex_color_id,
+                lit_id,
                 tracer: Tracer::new(gl),
             };
             this.tracer.regrid(gl);
@@ -621,8 +695,7 @@ impl Drawable {
         egui::Window::new("Controls").show(ctx, |ui| {
             // TODO
             // if ui.button("Quit").clicked() {}
-            ui.add(egui::Slider::new(&mut self.tilt, -90.0..=90.0).text("Tilt"));
-            ui.add(egui::Slider::new(&mut self.turn, -180.0..=180.0).text("Turn"));
+            self.camera.ui(ui);
             self.tracer.ui(ui, gl);
         });
     }
@@ -632,25 +705,78 @@ impl Drawable {
             // Set up state shared across lines.
             gl.viewport(0, 0, width as i32, height as i32);
             gl.use_program(Some(self.program));
-            gl.uniform_1_f32(Some(&self.tilt_id), self.tilt);
-            gl.uniform_1_f32(Some(&self.turn_id), self.turn);
-            gl.uniform_1_f32(
-                Some(&self.x_scale_id),
-                (height as f32 / width as f32).min(1.0f32),
+            gl.uniform_matrix_4_f32_slice(
+                Some(&self.transform_id),
+                false,
+                &self.camera.transform(width, height).to_gl(),
             );
-            gl.uniform_1_f32(
-                Some(&self.y_scale_id),
-                (width as f32 / height as f32).min(1.0f32),
+            gl.uniform_matrix_3_f32_slice(
+                Some(&self.normal_rotation_id),
+                false,
+                &self.camera.view().rotation_to_gl(),
             );
 
-            gl.uniform_3_f32(Some(&self.color_id), 0.5f32, 0.5f32, 0.5f32);
-            self.tracer.grid.draw(gl, glow::LINES);
+            // Depth testing, so that the filled surface hides what's
+            // behind it. Lines on the surface shouldn't be hidden by
+            // it, so push filled triangles back a little.
+            gl.clear(glow::DEPTH_BUFFER_BIT);
+            gl.enable(glow::DEPTH_TEST);
+            gl.depth_func(glow::LEQUAL);
+            gl.enable(glow::POLYGON_OFFSET_FILL);
+            gl.polygon_offset(2.0, 2.0);
+
+            let style = self.tracer.surface_style();
+            gl.uniform_1_i32(Some(&self.vertex_color_id), 1);
+            gl.uniform_1_i32(Some(&self.lit_id), style.shaded() as i32);
+            self.tracer.shaded.draw(gl, glow::TRIANGLES);
+            // Drawn over the same triangles as the shaded surface,
+            // which LEQUAL lets it win against.
+            self.tracer.distance_fill.draw(gl, glow::TRIANGLES);
+            gl.uniform_1_i32(Some(&self.lit_id), 0);
+            gl.disable(glow::POLYGON_OFFSET_FILL);
+
+            if style.wireframe() {
+                self.tracer.grid.draw(gl, glow::LINES);
+            }
+            self.tracer.exp_map.draw(gl, glow::LINES);
+            self.tracer.hyper.paths.draw(gl, glow::LINES);
+            self.tracer.objects.outlines.draw(gl, glow::LINES);
+            self.tracer.objects.rays.draw(gl, glow::LINES);
+            gl.uniform_1_i32(Some(&self.vertex_color_id), 0);
+
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 1.0f32, 1.0f32);
+            self.tracer.isolines.draw(gl, glow::LINES);
 
             gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 0.5f32, 0.5f32);
             self.tracer.paths.draw(gl, glow::LINES);
 
             gl.uniform_3_f32(Some(&self.color_id), 0.5f32, 01.0f32, 0.5f32);
             self.tracer.paths2.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 0.5f32, 0.8f32, 1.0f32);
+            self.tracer.wavefront.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 1.0f32, 0.3f32);
+            self.tracer.compare_paths.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 0.3f32, 1.0f32);
+            self.tracer.bvp_paths.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 0.3f32, 1.0f32, 1.0f32);
+            self.tracer.transport_glyphs.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 0.6f32, 0.1f32);
+            self.tracer.polygon_paths.draw(gl, glow::LINES);
+
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 1.0f32, 1.0f32);
+            self.tracer.conjugate_markers.draw(gl, glow::LINES);
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 0.8f32, 0.0f32);
+            self.tracer.caustic.draw(gl, glow::LINES);
+            gl.uniform_3_f32(Some(&self.color_id), 1.0f32, 0.2f32, 0.2f32);
+            self.tracer.cut_locus.draw(gl, glow::LINES);
+
+            // Leave the state as egui expects.
+            gl.disable(glow::DEPTH_TEST);
         }
     }
 
@@ -0,0 +1,61 @@
+//
+// mat3.rs: A 3x3 matrix to go with vec3.rs, mostly for holding
+// Hessians.
+//
+
+use crate::vec3::*;
+
+#[derive(Clone, Debug)]
+pub struct Mat3 {
+    // Row-major.
+    pub m: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub fn zero() -> Mat3 {
+        Mat3 { m: [[0.0; 3]; 3] }
+    }
+
+    // Build a symmetric matrix from its upper triangle.
+    pub fn symmetric(xx: f64, xy: f64, xz: f64, yy: f64, yz: f64, zz: f64) -> Mat3 {
+        Mat3 {
+            m: [[xx, xy, xz], [xy, yy, yz], [xz, yz, zz]],
+        }
+    }
+
+    pub fn mul_vec(&self, v: &Vec3) -> Vec3 {
+        let row = |r: &[f64; 3]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3 {
+            x: row(&self.m[0]),
+            y: row(&self.m[1]),
+            z: row(&self.m[2]),
+        }
+    }
+
+    pub fn trace(&self) -> f64 {
+        self.m[0][0] + self.m[1][1] + self.m[2][2]
+    }
+
+    // The adjugate (transposed cofactor matrix), which is the inverse
+    // times the determinant, but also exists for singular matrices.
+    pub fn adjugate(&self) -> Mat3 {
+        let m = &self.m;
+        let cofactor = |i: usize, j: usize| {
+            let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+            let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let mut adj = Mat3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                adj.m[j][i] = cofactor(i, j);
+            }
+        }
+        adj
+    }
+
+    // v^T M v.
+    pub fn quad_form(&self, v: &Vec3) -> f64 {
+        v.dot(&self.mul_vec(v))
+    }
+}
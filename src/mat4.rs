@@ -0,0 +1,60 @@
+//
+// mat4.rs: A 4x4 matrix, for the camera's homogeneous transforms.
+//
+
+use crate::vec3::*;
+
+#[derive(Clone, Debug)]
+pub struct Mat4 {
+    // Row-major.
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    // The view transform for a camera at eye, with the given
+    // (orthonormal) right, up and forward directions. The camera looks
+    // down its -Z axis, as OpenGL expects.
+    pub fn view(eye: &Vec3, right: &Vec3, up: &Vec3, forward: &Vec3) -> Mat4 {
+        let back = forward.scale(-1.0);
+        let row = |v: &Vec3| [v.x, v.y, v.z, -v.dot(eye)];
+        Mat4 {
+            m: [row(right), row(up), row(&back), [0.0, 0.0, 0.0, 1.0]],
+        }
+    }
+
+    // A perspective projection, scaling X and Y by x_scale and
+    // y_scale (the cotangents of half the fields of view), and mapping
+    // depths from near to far into the -1..1 clip range.
+    pub fn perspective(x_scale: f64, y_scale: f64, near: f64, far: f64) -> Mat4 {
+        let depth = near - far;
+        Mat4 {
+            m: [
+                [x_scale, 0.0, 0.0, 0.0],
+                [0.0, y_scale, 0.0, 0.0],
+                [0.0, 0.0, (far + near) / depth, 2.0 * far * near / depth],
+                [0.0, 0.0, -1.0, 0.0],
+            ],
+        }
+    }
+
+    pub fn mul(&self, rhs: &Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+
+    // Column-major, for uploading as a uniform.
+    pub fn to_gl(&self) -> [f32; 16] {
+        std::array::from_fn(|idx| self.m[idx % 4][idx / 4] as f32)
+    }
+
+    // The top-left 3x3 part, column-major, for transforming normals
+    // by a rotation.
+    pub fn rotation_to_gl(&self) -> [f32; 9] {
+        std::array::from_fn(|idx| self.m[idx % 3][idx / 3] as f32)
+    }
+}
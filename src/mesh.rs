@@ -0,0 +1,75 @@
+//
+// mesh.rs: A triangle mesh sampling the surface, for things that need
+// the whole surface rather than individual paths, such as distance
+// fields.
+//
+
+use crate::vec3::*;
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    // Add another mesh's triangles to this one, unconnected.
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.vertices.len();
+        self.vertices.extend(other.vertices.iter().cloned());
+        self.triangles
+            .extend(other.triangles.iter().map(|tri| tri.map(|v| v + offset)));
+    }
+
+    // For each vertex, the triangles it's a corner of.
+    pub fn vertex_triangles(&self) -> Vec<Vec<usize>> {
+        let mut result = vec![Vec::new(); self.vertices.len()];
+        for (idx, tri) in self.triangles.iter().enumerate() {
+            for v in tri.iter() {
+                result[*v].push(idx);
+            }
+        }
+        result
+    }
+
+    // The line segments where a per-vertex value, interpolated
+    // linearly over each triangle, crosses the given level. Triangles
+    // with non-finite values are skipped.
+    pub fn contour(&self, values: &[f64], level: f64) -> Vec<(Vec3, Vec3)> {
+        let mut segments = Vec::new();
+        for tri in self.triangles.iter() {
+            let vals = tri.map(|v| values[v]);
+            if vals.iter().any(|v| !v.is_finite()) {
+                continue;
+            }
+            // Find the edges whose ends are on opposite sides of the
+            // level. There are either none or two.
+            let mut crossings = Vec::new();
+            for i in 0..3 {
+                let j = (i + 1) % 3;
+                let (a, b) = (vals[i] - level, vals[j] - level);
+                if (a < 0.0) != (b < 0.0) {
+                    let t = a / (a - b);
+                    let (p, q) = (&self.vertices[tri[i]], &self.vertices[tri[j]]);
+                    crossings.push(p.add(&q.sub(p).scale(t)));
+                }
+            }
+            if let [a, b] = &crossings[..] {
+                segments.push((a.clone(), b.clone()));
+            }
+        }
+        segments
+    }
+
+    // Write as a Wavefront OBJ file (indices there are 1-based).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_obj(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        for p in self.vertices.iter() {
+            writeln!(w, "v {} {} {}", p.x, p.y, p.z)?;
+        }
+        for [a, b, c] in self.triangles.iter() {
+            writeln!(w, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//
+// mesher.rs: Build a triangle mesh of an implicit surface f(p) = 0
+// inside a box, by marching tetrahedra.
+//
+// Unlike tracing the grid over the surface, this doesn't care how the
+// surface is shaped: Overhangs, multiple sheets and tubes like the
+// wormhole's throat all come out the same way.
+//
+// The box is split into cubes, and each cube into six tetrahedra
+// around its main diagonal. Within a tetrahedron the surface is
+// approximated by a single triangle or quad, through the points where
+// f changes sign along the edges. Using tetrahedra rather than whole
+// cubes (marching cubes) avoids the ambiguous cases and big case
+// tables, and as every cube is split the same way, neighbouring
+// faces match up and the mesh is watertight.
+//
+
+use std::collections::HashMap;
+
+use crate::mesh::*;
+use crate::vec3::*;
+
+// The tetrahedra making up a cube, by corner. Corner c is at offset
+// (c & 1, (c >> 1) & 1, (c >> 2) & 1). All share the diagonal from
+// corner 0 to corner 7, and go around it in order.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 7, 1, 3],
+    [0, 7, 3, 2],
+    [0, 7, 2, 6],
+    [0, 7, 6, 4],
+    [0, 7, 4, 5],
+    [0, 7, 5, 1],
+];
+
+// Mesh the surface where f is zero, between the corners min and max of
+// a box, using n cubes along each side. Triangles are wound so that
+// their normals point towards increasing f.
+pub fn mesh(f: impl Fn(&Vec3) -> f64, min: &Vec3, max: &Vec3, n: usize) -> Mesh {
+    let size = max.sub(min);
+    let point = |i: usize, j: usize, k: usize| Vec3 {
+        x: min.x + size.x * i as f64 / n as f64,
+        y: min.y + size.y * j as f64 / n as f64,
+        z: min.z + size.z * k as f64 / n as f64,
+    };
+    let index = |i: usize, j: usize, k: usize| (k * (n + 1) + j) * (n + 1) + i;
+
+    // Sample the field once per lattice point.
+    let mut values = vec![0.0; (n + 1) * (n + 1) * (n + 1)];
+    for k in 0..=n {
+        for j in 0..=n {
+            for i in 0..=n {
+                values[index(i, j, k)] = f(&point(i, j, k));
+            }
+        }
+    }
+
+    let mut builder = Builder {
+        mesh: Mesh::default(),
+        edge_vertices: HashMap::new(),
+    };
+    for k in 0..n {
+        for j in 0..n {
+            for i in 0..n {
+                let corners: [usize; 8] = std::array::from_fn(|c| {
+                    index(i + (c & 1), j + ((c >> 1) & 1), k + ((c >> 2) & 1))
+                });
+                for tet in TETRAHEDRA.iter() {
+                    let ids = tet.map(|c| corners[c]);
+                    let points =
+                        tet.map(|c| point(i + (c & 1), j + ((c >> 1) & 1), k + ((c >> 2) & 1)));
+                    let vals = ids.map(|id| values[id]);
+                    if vals.iter().all(|v| v.is_finite()) {
+                        builder.tetrahedron(&ids, &points, &vals);
+                    }
+                }
+            }
+        }
+    }
+    builder.mesh
+}
+
+struct Builder {
+    mesh: Mesh,
+    // Vertices already created on lattice edges, keyed by the
+    // lattice points at the edge's ends (lowest first), so that
+    // neighbouring tetrahedra share them.
+    edge_vertices: HashMap<(usize, usize), usize>,
+}
+
+impl Builder {
+    // The vertex where the surface crosses the edge between two
+    // corners of a tetrahedron.
+    fn edge_vertex(
+        &mut self,
+        ids: &[usize; 4],
+        points: &[Vec3; 4],
+        vals: &[f64; 4],
+        a: usize,
+        b: usize,
+    ) -> usize {
+        let key = (ids[a].min(ids[b]), ids[a].max(ids[b]));
+        if let Some(v) = self.edge_vertices.get(&key) {
+            return *v;
+        }
+        // Interpolate from the lower id, so the result doesn't depend
+        // on which tetrahedron asked first.
+        let (a, b) = if ids[a] < ids[b] { (a, b) } else { (b, a) };
+        let t = vals[a] / (vals[a] - vals[b]);
+        let p = points[a].add(&points[b].sub(&points[a]).scale(t));
+        let v = self.mesh.vertices.len();
+        self.mesh.vertices.push(p);
+        self.edge_vertices.insert(key, v);
+        v
+    }
+
+    fn tetrahedron(&mut self, ids: &[usize; 4], points: &[Vec3; 4], vals: &[f64; 4]) {
+        let (inside, outside): (Vec<usize>, Vec<usize>) = (0..4).partition(|c| vals[*c] < 0.0);
+        // The edges the surface crosses, as (inside, outside) corners.
+        let polygon = match (inside.len(), outside.len()) {
+            // One corner is separated from the other three.
+            (1, 3) => vec![
+                (inside[0], outside[0]),
+                (inside[0], outside[1]),
+                (inside[0], outside[2]),
+            ],
+            (3, 1) => vec![
+                (inside[0], outside[0]),
+                (inside[1], outside[0]),
+                (inside[2], outside[0]),
+            ],
+            // The surface cuts four edges, making a quad. Going around
+            // it, consecutive edges share a corner.
+            (2, 2) => vec![
+                (inside[0], outside[0]),
+                (inside[0], outside[1]),
+                (inside[1], outside[1]),
+                (inside[1], outside[0]),
+            ],
+            _ => return,
+        };
+
+        // Work out the winding from the edges' midpoints rather than
+        // the vertices themselves, as the vertices may coincide if the
+        // surface passes through a corner.
+        let mid = |(a, b): (usize, usize)| points[a].add(&points[b]).scale(0.5);
+        let (m0, m1, m2) = (mid(polygon[0]), mid(polygon[1]), mid(polygon[2]));
+        let normal = m1.sub(&m0).cross(&m2.sub(&m0));
+        let towards = points[polygon[0].1].sub(&points[polygon[0].0]);
+        let flip = normal.dot(&towards) < 0.0;
+
+        let mut verts = polygon
+            .iter()
+            .map(|(a, b)| self.edge_vertex(ids, points, vals, *a, *b))
+            .collect::<Vec<_>>();
+        if flip {
+            verts.reverse();
+        }
+        for idx in 1..verts.len() - 1 {
+            self.mesh
+                .triangles
+                .push([verts[0], verts[idx], verts[idx + 1]]);
+        }
+    }
+}
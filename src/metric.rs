@@ -0,0 +1,562 @@
+//
+// metric.rs: Spaces defined intrinsically, by a metric tensor g_ij
+// over a 2D coordinate chart, rather than as a surface embedded in
+// 3D. This follows maths2.md: Geodesics come from the Christoffel
+// symbols of the metric, with no reference to an embedding, so spaces
+// that can't be embedded in R^3 (such as the whole hyperbolic plane)
+// work just as well.
+//
+// Points in the chart are represented as Vec3s with z = 0, so that
+// paths can be drawn like any other.
+//
+
+use crate::dual::{Dual, HyperDual, Scalar};
+use crate::geodesic;
+use crate::surface::Domain;
+use crate::vec3::*;
+
+////////////////////////////////////////////////////////////////////////
+// The Metric trait.
+//
+
+pub trait Metric {
+    // Name shown in the UI.
+    fn label(&self) -> &str;
+
+    // The metric tensor at (x, y), with its first derivatives, taking
+    // x and y as variables 0 and 1.
+    fn with_derivatives(&self, x: f64, y: f64) -> [[Dual; 2]; 2];
+
+    // The same, with second derivatives too, as needed for curvature.
+    fn with_second_derivatives(&self, x: f64, y: f64) -> [[HyperDual; 2]; 2];
+
+    // Region of the chart to draw and trace over.
+    fn domain(&self) -> Domain {
+        Domain::default()
+    }
+
+    // Whether a point in the domain is part of the space. The metric
+    // is only used at points where this is true.
+    fn contains(&self, _p: &Vec3) -> bool {
+        true
+    }
+
+    // Map a point between this chart and a reference chart for the
+    // space. A space offering several charts (switched between in
+    // `ui`) uses these to keep the rays in place when the chart
+    // changes.
+    fn to_reference(&self, p: &Vec3) -> Vec3 {
+        p.clone()
+    }
+
+    fn to_chart(&self, p: &Vec3) -> Vec3 {
+        p.clone()
+    }
+
+    // Lines to draw as the grid, with roughly n in each direction, or
+    // None to use the chart's coordinate lines.
+    fn grid(&self, _n: usize) -> Option<Vec<Vec<Vec3>>> {
+        None
+    }
+
+    // Any metric-specific controls. Returns true if the metric
+    // changed.
+    fn ui(&mut self, _ui: &mut egui::Ui) -> bool {
+        false
+    }
+}
+
+// The metrics that come with the program, in the order they appear in
+// the UI.
+pub fn builtin_metrics() -> Vec<Box<dyn Metric>> {
+    vec![
+        Box::new(Flat),
+        Box::new(StereographicSphere),
+        Box::new(ConformalBump { height: 0.5 }),
+        Box::new(Hyperbolic {
+            model: HyperbolicModel::PoincareDisc,
+        }),
+        Box::new(EllisPlane { throat: 1.0 }),
+    ]
+}
+
+////////////////////////////////////////////////////////////////////////
+// Geodesics and curvature.
+//
+// With the metric's inverse g^kl, the Christoffel symbols are
+//
+//   Gamma^k_ij = 1/2 g^kl (d_i g_lj + d_j g_li - d_l g_ij)
+//
+// and a geodesic x(s) satisfies
+//
+//   x''^k = -Gamma^k_ij x'^i x'^j
+//
+
+// The inverse of a 2x2 matrix, if it's positive definite, as a metric
+// must be. Outside the region where it's valid (e.g. off the edge of
+// the Poincare disc) it usually isn't.
+fn inverse(g: &[[f64; 2]; 2]) -> Option<[[f64; 2]; 2]> {
+    let det = g[0][0] * g[1][1] - g[0][1] * g[1][0];
+    if !(det > 0.0 && g[0][0] > 0.0 && det.is_finite()) {
+        return None;
+    }
+    Some([
+        [g[1][1] / det, -g[0][1] / det],
+        [-g[1][0] / det, g[0][0] / det],
+    ])
+}
+
+fn values<S: Copy>(g: &[[S; 2]; 2], value: impl Fn(S) -> f64) -> [[f64; 2]; 2] {
+    g.map(|row| row.map(&value))
+}
+
+// Gamma^k_ij, indexed [k][i][j], from the metric and its first
+// derivatives, given as dg[m][i][j] = d_m g_ij.
+fn christoffel_from(g_inv: &[[f64; 2]; 2], dg: &[[[f64; 2]; 2]; 2]) -> [[[f64; 2]; 2]; 2] {
+    std::array::from_fn(|k| {
+        std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                (0..2)
+                    .map(|l| 0.5 * g_inv[k][l] * (dg[i][l][j] + dg[j][l][i] - dg[l][i][j]))
+                    .sum()
+            })
+        })
+    })
+}
+
+// The Christoffel symbols at p, indexed [k][i][j], or None if the
+// metric isn't valid there.
+pub fn christoffel(metric: &dyn Metric, p: &Vec3) -> Option<[[[f64; 2]; 2]; 2]> {
+    let g = metric.with_derivatives(p.x, p.y);
+    let g_inv = inverse(&values(&g, |c| c.v))?;
+    let dg = std::array::from_fn(|m| values(&g, |c| c.d[m]));
+    Some(christoffel_from(&g_inv, &dg))
+}
+
+// Whether the metric is valid (finite and positive definite) at p.
+pub fn valid_at(metric: &dyn Metric, p: &Vec3) -> bool {
+    metric.contains(p) && inverse(&values(&metric.with_derivatives(p.x, p.y), |c| c.v)).is_some()
+}
+
+// |v|^2 = g_ij v^i v^j at p.
+pub fn norm_squared(metric: &dyn Metric, p: &Vec3, v: &Vec3) -> f64 {
+    let g = values(&metric.with_derivatives(p.x, p.y), |c| c.v);
+    let v = [v.x, v.y];
+    (0..2)
+        .flat_map(|i| (0..2).map(move |j| (i, j)))
+        .map(|(i, j)| g[i][j] * v[i] * v[j])
+        .sum()
+}
+
+// The vector at p pointing in the chart direction v, scaled to unit
+// length in the metric.
+pub fn unit(metric: &dyn Metric, p: &Vec3, v: &Vec3) -> Vec3 {
+    v.scale(norm_squared(metric, p, v).sqrt().recip())
+}
+
+// The Gaussian curvature at p, from the Riemann tensor:
+// K = R_0101 / det g, where
+//
+//   R^a_bcd = d_c Gamma^a_db - d_d Gamma^a_cb
+//             + Gamma^a_ce Gamma^e_db - Gamma^a_de Gamma^e_cb
+//
+pub fn gaussian_curvature(metric: &dyn Metric, p: &Vec3) -> Option<f64> {
+    let g2 = metric.with_second_derivatives(p.x, p.y);
+    let g = values(&g2, |c| c.v);
+    let g_inv = inverse(&g)?;
+    let dg: [[[f64; 2]; 2]; 2] = std::array::from_fn(|m| values(&g2, |c| c.d[m]));
+    let gamma = christoffel_from(&g_inv, &dg);
+
+    // d_m Gamma^k_ij, indexed [m][k][i][j], by the product rule, with
+    // d_m g^kl = -g^ka d_m g_ab g^bl.
+    let d_gamma: [[[[f64; 2]; 2]; 2]; 2] = std::array::from_fn(|m| {
+        let d_inv: [[f64; 2]; 2] = std::array::from_fn(|k| {
+            std::array::from_fn(|l| {
+                let mut sum = 0.0;
+                for a in 0..2 {
+                    for b in 0..2 {
+                        sum -= g_inv[k][a] * dg[m][a][b] * g_inv[b][l];
+                    }
+                }
+                sum
+            })
+        });
+        std::array::from_fn(|k| {
+            std::array::from_fn(|i| {
+                std::array::from_fn(|j| {
+                    (0..2)
+                        .map(|l| {
+                            let a = dg[i][l][j] + dg[j][l][i] - dg[l][i][j];
+                            let d_a = g2[l][j].h[m][i] + g2[l][i].h[m][j] - g2[i][j].h[m][l];
+                            0.5 * (d_inv[k][l] * a + g_inv[k][l] * d_a)
+                        })
+                        .sum()
+                })
+            })
+        })
+    });
+
+    let riemann = |a: usize, b: usize, c: usize, d: usize| {
+        d_gamma[c][a][d][b] - d_gamma[d][a][c][b]
+            + (0..2)
+                .map(|e| gamma[a][c][e] * gamma[e][d][b] - gamma[a][d][e] * gamma[e][c][b])
+                .sum::<f64>()
+    };
+    let lowered: f64 = (0..2).map(|a| g[0][a] * riemann(a, 1, 0, 1)).sum();
+    Some(lowered / (g[0][0] * g[1][1] - g[0][1] * g[1][0]))
+}
+
+// The geodesic equation for a metric, for the integrators in
+// geodesic.rs.
+pub struct Geodesic<'a>(pub &'a dyn Metric);
+
+impl geodesic::Equation<Vec3> for Geodesic<'_> {
+    fn acceleration(&self, p: &Vec3, v: &Vec3) -> Vec3 {
+        let Some(gamma) = christoffel(self.0, p) else {
+            // Let the integrator see that it's gone wrong.
+            return Vec3 {
+                x: f64::NAN,
+                y: f64::NAN,
+                z: 0.0,
+            };
+        };
+        let v = [v.x, v.y];
+        let accel = |k: usize| {
+            let mut sum = 0.0;
+            for i in 0..2 {
+                for j in 0..2 {
+                    sum -= gamma[k][i][j] * v[i] * v[j];
+                }
+            }
+            sum
+        };
+        Vec3 {
+            x: accel(0),
+            y: accel(1),
+            z: 0.0,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Built-in metrics.
+//
+
+// Implements the derivative methods for a type with a method
+// `eval<S: Scalar>(&self, x: S, y: S) -> [[S; 2]; 2]`.
+macro_rules! autodiff_metric {
+    () => {
+        fn with_derivatives(&self, x: f64, y: f64) -> [[Dual; 2]; 2] {
+            self.eval(Dual::var(x, 0), Dual::var(y, 1))
+        }
+
+        fn with_second_derivatives(&self, x: f64, y: f64) -> [[HyperDual; 2]; 2] {
+            self.eval(HyperDual::var(x, 0), HyperDual::var(y, 1))
+        }
+    };
+}
+
+// A metric that's a multiple of the Euclidean one, e^(2 phi) delta_ij.
+// Conformal metrics preserve angles in the chart.
+fn conformal<S: Scalar>(scale: S) -> [[S; 2]; 2] {
+    let zero = S::from_f64(0.0);
+    [[scale, zero], [zero, scale]]
+}
+
+pub struct Flat;
+
+impl Flat {
+    fn eval<S: Scalar>(&self, _x: S, _y: S) -> [[S; 2]; 2] {
+        conformal(S::from_f64(1.0))
+    }
+}
+
+impl Metric for Flat {
+    fn label(&self) -> &str {
+        "Flat plane"
+    }
+
+    autodiff_metric!();
+}
+
+// The unit sphere, seen through stereographic projection from the
+// south pole: The chart's origin is the north pole, the unit circle
+// is the equator, and the south pole is off at infinity.
+pub struct StereographicSphere;
+
+impl StereographicSphere {
+    fn eval<S: Scalar>(&self, x: S, y: S) -> [[S; 2]; 2] {
+        let denom = x * x + y * y + 1.0;
+        conformal(S::from_f64(4.0) / (denom * denom))
+    }
+}
+
+impl Metric for StereographicSphere {
+    fn label(&self) -> &str {
+        "Sphere (stereographic)"
+    }
+
+    autodiff_metric!();
+
+    fn domain(&self) -> Domain {
+        Domain {
+            x_min: -2.0,
+            x_max: 2.0,
+            y_min: -2.0,
+            y_max: 2.0,
+        }
+    }
+}
+
+// A flat plane with a localised bump (or dip) in the metric at the
+// origin, which focuses (or spreads) rays passing through it.
+pub struct ConformalBump {
+    height: f64,
+}
+
+impl ConformalBump {
+    fn eval<S: Scalar>(&self, x: S, y: S) -> [[S; 2]; 2] {
+        let r2 = x * x + y * y;
+        let phi = (r2 * -10.0).exp() * self.height;
+        conformal((phi * 2.0).exp())
+    }
+}
+
+impl Metric for ConformalBump {
+    fn label(&self) -> &str {
+        "Conformal bump"
+    }
+
+    autodiff_metric!();
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.add(egui::Slider::new(&mut self.height, -1.0..=1.0).text("Bump height"))
+            .changed()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// The hyperbolic plane: The complete surface of constant curvature -1,
+// which can't be embedded in R^3 as a whole. It has several standard
+// models, each a chart with its own metric, and they're all the same
+// space, so switching between them keeps the same rays.
+//
+// The reference chart is the Poincare disc.
+//
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HyperbolicModel {
+    // The unit disc, with geodesics as circular arcs meeting the edge
+    // at right angles. Conformal.
+    PoincareDisc,
+    // The region y > 0, with geodesics as vertical lines and
+    // semicircles centred on the x axis. Conformal.
+    HalfPlane,
+    // The unit disc, with geodesics as straight chords. Not conformal.
+    Klein,
+}
+
+impl HyperbolicModel {
+    const ALL: [HyperbolicModel; 3] = [
+        HyperbolicModel::PoincareDisc,
+        HyperbolicModel::HalfPlane,
+        HyperbolicModel::Klein,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            HyperbolicModel::PoincareDisc => "Poincare disc",
+            HyperbolicModel::HalfPlane => "Upper half-plane",
+            HyperbolicModel::Klein => "Beltrami-Klein",
+        }
+    }
+}
+
+pub struct Hyperbolic {
+    model: HyperbolicModel,
+}
+
+impl Hyperbolic {
+    fn eval<S: Scalar>(&self, x: S, y: S) -> [[S; 2]; 2] {
+        match self.model {
+            HyperbolicModel::PoincareDisc => {
+                let denom = -(x * x + y * y - 1.0);
+                conformal(S::from_f64(4.0) / (denom * denom))
+            }
+            HyperbolicModel::HalfPlane => conformal(S::from_f64(1.0) / (y * y)),
+            HyperbolicModel::Klein => {
+                // delta_ij / (1 - r^2) + x_i x_j / (1 - r^2)^2.
+                let s = -(x * x + y * y - 1.0);
+                let s2 = s * s;
+                let off = x * y / s2;
+                let diag = S::from_f64(1.0) / s;
+                [[x * x / s2 + diag, off], [off, y * y / s2 + diag]]
+            }
+        }
+    }
+}
+
+impl Metric for Hyperbolic {
+    fn label(&self) -> &str {
+        "Hyperbolic plane"
+    }
+
+    autodiff_metric!();
+
+    fn domain(&self) -> Domain {
+        match self.model {
+            HyperbolicModel::HalfPlane => Domain {
+                x_min: -2.0,
+                x_max: 2.0,
+                y_min: 0.0,
+                y_max: 4.0,
+            },
+            _ => Domain::default(),
+        }
+    }
+
+    fn contains(&self, p: &Vec3) -> bool {
+        match self.model {
+            HyperbolicModel::HalfPlane => p.y > 0.0,
+            _ => p.x * p.x + p.y * p.y < 1.0,
+        }
+    }
+
+    fn to_reference(&self, p: &Vec3) -> Vec3 {
+        match self.model {
+            HyperbolicModel::PoincareDisc => p.clone(),
+            // w = (z - i) / (z + i).
+            HyperbolicModel::HalfPlane => {
+                let (a, b) = (p.x, p.y - 1.0);
+                let (c, d) = (p.x, p.y + 1.0);
+                let denom = c * c + d * d;
+                Vec3 {
+                    x: (a * c + b * d) / denom,
+                    y: (b * c - a * d) / denom,
+                    z: 0.0,
+                }
+            }
+            HyperbolicModel::Klein => p.scale((1.0 + (1.0 - p.dot(p)).sqrt()).recip()),
+        }
+    }
+
+    fn to_chart(&self, p: &Vec3) -> Vec3 {
+        match self.model {
+            HyperbolicModel::PoincareDisc => p.clone(),
+            // z = i (1 + w) / (1 - w).
+            HyperbolicModel::HalfPlane => {
+                let (a, b) = (1.0 + p.x, p.y);
+                let (c, d) = (1.0 - p.x, -p.y);
+                let denom = c * c + d * d;
+                let (re, im) = ((a * c + b * d) / denom, (b * c - a * d) / denom);
+                Vec3 {
+                    x: -im,
+                    y: re,
+                    z: 0.0,
+                }
+            }
+            HyperbolicModel::Klein => p.scale(2.0 / (1.0 + p.dot(p))),
+        }
+    }
+
+    // A grid of geodesics, evenly spaced in distance from the centre.
+    // They're easiest to write down as straight chords in the Klein
+    // model, from where they're mapped into the current one.
+    fn grid(&self, n: usize) -> Option<Vec<Vec<Vec3>>> {
+        // The furthest lines from the centre, in hyperbolic distance.
+        const EXTENT: f64 = 3.0;
+        const LINE_POINTS: usize = 200;
+
+        let klein = Hyperbolic {
+            model: HyperbolicModel::Klein,
+        };
+        let domain = self.domain();
+        let mut lines = Vec::new();
+        for idx in 0..=n {
+            let offset = (EXTENT * (2.0 * idx as f64 / n as f64 - 1.0)).tanh();
+            let half_len = (1.0 - offset * offset).sqrt();
+            for vertical in [false, true] {
+                let mut line: Vec<Vec3> = Vec::new();
+                for step in 0..=LINE_POINTS {
+                    // Also spaced out evenly by distance along the
+                    // line, rather than in the chart.
+                    let u = 2.0 * step as f64 / LINE_POINTS as f64 - 1.0;
+                    let along = half_len * (2.0 * EXTENT * u).tanh();
+                    let (x, y) = if vertical {
+                        (offset, along)
+                    } else {
+                        (along, offset)
+                    };
+                    let p = self.to_chart(&klein.to_reference(&Vec3 { x, y, z: 0.0 }));
+                    if domain.contains(&p) && self.contains(&p) {
+                        line.push(p);
+                    } else if !line.is_empty() {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                }
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+            }
+        }
+        Some(lines)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        egui::ComboBox::from_label("Model")
+            .selected_text(self.model.label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for m in HyperbolicModel::ALL {
+                    changed |= ui.selectable_value(&mut self.model, m, m.label()).changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// The equatorial plane of an Ellis wormhole with a throat of the given
+// radius, in (l, phi): l runs from one side of the throat, at l = 0,
+// to the other. wormhole.rs traces light through the whole wormhole
+// with it.
+//
+
+pub struct EllisPlane {
+    pub throat: f64,
+}
+
+impl EllisPlane {
+    fn eval<S: Scalar>(&self, l: S, _phi: S) -> [[S; 2]; 2] {
+        let zero = S::from_f64(0.0);
+        [
+            [S::from_f64(1.0), zero],
+            [zero, l * l + self.throat * self.throat],
+        ]
+    }
+}
+
+impl Metric for EllisPlane {
+    fn label(&self) -> &str {
+        "Wormhole (equatorial plane)"
+    }
+
+    autodiff_metric!();
+
+    // l across, phi up.
+    fn domain(&self) -> Domain {
+        Domain {
+            x_min: -4.0,
+            x_max: 4.0,
+            y_min: -std::f64::consts::PI,
+            y_max: std::f64::consts::PI,
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.add(egui::Slider::new(&mut self.throat, 0.1..=2.0).text("Throat radius"))
+            .changed()
+    }
+}
@@ -0,0 +1,441 @@
+//
+// objects.rs: Coloured discs placed in the space, for rays to hit, so
+// that we can see the space the way one of its inhabitants would.
+//
+// An object's centre is given in X/Y, like the ray origin, and placed
+// on the surface (or in the chart) by the tracer. Rays only ever see
+// an object's edge, so all we need is which object a ray meets first.
+//
+// Objects holds the list along with its controls and drawing. It
+// traces through the Space trait, so the tracer only has to say when
+// to redraw.
+//
+
+use glow::Context;
+
+use crate::colour;
+use crate::polyline;
+use crate::shape::{self, Shape};
+use crate::surface::Domain;
+use crate::vec3::*;
+
+// What objects need from the tracer.
+pub trait Space {
+    // Where a point given in X/Y is, on the surface or in the chart.
+    // None if there's nothing there.
+    fn place(&self, xy: (f64, f64)) -> Option<Vec3>;
+    // The distance between two nearby points.
+    fn local_dist(&self, a: &Vec3, b: &Vec3) -> f64;
+    // Trace a ray from the point at xy in direction angle (in
+    // degrees), until it leaves the domain or is max_len long. None if
+    // there's nothing there to start from.
+    fn ray_from(&self, xy: (f64, f64), angle: f64, max_len: f64) -> Option<Vec<Vec3>>;
+}
+
+pub struct Object {
+    // Centre, in X/Y.
+    pub centre: (f64, f64),
+    // Geodesic radius.
+    pub radius: f64,
+    pub colour: [f32; 3],
+}
+
+// A few objects scattered around the middle of the usual domain.
+pub fn default_objects() -> Vec<Object> {
+    const RADIUS: f64 = 0.08;
+    [
+        ((0.0, 0.6), [0.9, 0.2, 0.2]),
+        ((0.5, 0.2), [0.2, 0.8, 0.2]),
+        ((-0.6, 0.3), [0.3, 0.4, 0.95]),
+        ((0.6, -0.5), [0.95, 0.85, 0.2]),
+    ]
+    .into_iter()
+    .map(|(centre, colour)| Object {
+        centre,
+        radius: RADIUS,
+        colour,
+    })
+    .collect()
+}
+
+// Where a ray first meets an object: Which object, and the index of
+// the end of the path's segment that meets it.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub object: usize,
+    pub index: usize,
+}
+
+// An image of an object: A run of neighbouring rays that all hit it
+// first, given by their indices.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub object: usize,
+    pub rays: Vec<usize>,
+}
+
+// Split the objects hit by a sequence of rays, in order of angle, into
+// images. If wraps, the last ray is next to the first. An object with
+// more than one image is being lensed.
+pub fn images(hits: &[Option<usize>], wraps: bool) -> Vec<Image> {
+    let mut images: Vec<Image> = Vec::new();
+    for (idx, hit) in hits.iter().enumerate() {
+        let Some(object) = *hit else {
+            continue;
+        };
+        match images.last_mut() {
+            Some(image) if image.object == object && image.rays.last() == Some(&(idx - 1)) => {
+                image.rays.push(idx)
+            }
+            _ => images.push(Image {
+                object,
+                rays: vec![idx],
+            }),
+        }
+    }
+    // Join an image running off the end to one running on at the
+    // start.
+    if wraps && images.len() > 1 {
+        let (first, last) = (&images[0], &images[images.len() - 1]);
+        if first.object == last.object
+            && first.rays[0] == 0
+            && last.rays.last() == Some(&(hits.len() - 1))
+        {
+            let last = images.pop().unwrap();
+            images[0].rays.splice(0..0, last.rays);
+        }
+    }
+    images
+}
+
+// The number of images of each of count objects.
+pub fn image_counts(images: &[Image], count: usize) -> Vec<usize> {
+    let mut counts = vec![0; count];
+    for image in images.iter() {
+        counts[image.object] += 1;
+    }
+    counts
+}
+
+// Find the first object along path. centres are the objects' centres,
+// placed in the same space as the path (None if an object couldn't
+// be), and dist gives the distance between nearby points.
+//
+// Each segment is tested at its closest approach to the centre, so
+// that a ray can't step over the edge of a small object. If a segment
+// hits more than one object, the first is the one whose closest
+// approach comes first along it.
+pub fn first_hit(
+    path: &[Vec3],
+    objects: &[Object],
+    centres: &[Option<Vec3>],
+    dist: impl Fn(&Vec3, &Vec3) -> f64,
+) -> Option<Hit> {
+    (0..path.len()).find_map(|index| {
+        let segment = &path[index.saturating_sub(1)..=index];
+        objects
+            .iter()
+            .zip(centres.iter())
+            .enumerate()
+            .filter_map(|(idx, (object, centre))| {
+                let c = centre.as_ref()?;
+                let closest = polyline::closest_point(segment, c)?;
+                (dist(&closest.point, c) < object.radius).then_some((idx, closest.arc_len))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(object, _)| Hit { object, index })
+    })
+}
+
+////////////////////////////////////////////////////////////////////////
+// The objects in the UI: Their controls, outlines and the rays that
+// hit them.
+//
+
+// Radius of newly-added objects.
+const NEW_RADIUS: f64 = 0.08;
+
+// What the next picked point is for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Placing {
+    Move(usize),
+    Add,
+}
+
+pub struct Objects {
+    // Outlines of the objects, and the rays that hit them, in their
+    // colours.
+    pub outlines: Shape,
+    pub rays: Shape,
+    list: Vec<Object>,
+    show: bool,
+    // Stop the fans' rays at the first object they hit.
+    stop_at: bool,
+    // How many images of each object the fans show.
+    images: Vec<usize>,
+    // Set while waiting for a point to be picked on the surface.
+    placing: Option<Placing>,
+}
+
+impl Objects {
+    pub fn new(gl: &Context) -> Objects {
+        Objects {
+            outlines: Shape::new_colored(gl),
+            rays: Shape::new_colored(gl),
+            list: default_objects(),
+            show: false,
+            stop_at: false,
+            images: Vec::new(),
+            placing: None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn colour(&self, object: usize) -> [f32; 3] {
+        self.list[object].colour
+    }
+
+    // Whether the next picked point is for an object.
+    pub fn placing(&self) -> bool {
+        self.placing.is_some()
+    }
+
+    pub fn stop_placing(&mut self) {
+        self.placing = None;
+    }
+
+    // Move or add the object waiting for a picked point to p. Returns
+    // whether there was one.
+    pub fn place(&mut self, p: (f64, f64)) -> bool {
+        match self.placing.take() {
+            Some(Placing::Move(idx)) => self.list[idx].centre = p,
+            Some(Placing::Add) => self.add(p),
+            None => return false,
+        }
+        true
+    }
+
+    fn add(&mut self, centre: (f64, f64)) {
+        let colour = colour::cyclic(self.list.len() as f64 * 0.3);
+        self.list.push(Object {
+            centre,
+            radius: NEW_RADIUS,
+            colour,
+        });
+    }
+
+    // The controls for placing objects, with how many images of each
+    // the fans show. Objects can be placed by picking a point on the
+    // surface, or added at origin. Returns whether the rays need
+    // tracing again.
+    pub fn ui(&mut self, ui: &mut egui::Ui, domain: &Domain, origin: (f64, f64)) -> bool {
+        let mut changed = ui.checkbox(&mut self.show, "Show").changed();
+        changed |= ui
+            .checkbox(&mut self.stop_at, "Stop rays at objects")
+            .changed();
+        let mut remove = None;
+        for (idx, object) in self.list.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.color_edit_button_rgb(&mut object.colour).changed();
+                ui.label(format!("Object {}", idx + 1));
+                toggle_placing(ui, &mut self.placing, Placing::Move(idx), "Pick");
+                if ui.button("Remove").clicked() {
+                    remove = Some(idx);
+                }
+                match self.images.get(idx) {
+                    Some(&count) if count > 1 => {
+                        ui.colored_label(egui::Color32::YELLOW, format!("{} images", count));
+                    }
+                    Some(1) => {
+                        ui.label("1 image");
+                    }
+                    _ => {}
+                }
+            });
+            let (x, y) = &mut object.centre;
+            changed |= ui
+                .add(egui::Slider::new(x, domain.x_min..=domain.x_max).text("X"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(y, domain.y_min..=domain.y_max).text("Y"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut object.radius, 0.01..=0.5).text("Radius"))
+                .changed();
+        }
+        if let Some(idx) = remove {
+            self.list.remove(idx);
+            // Indices after it have shifted.
+            self.placing = None;
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add at ray origin").clicked() {
+                self.add(origin);
+                changed = true;
+            }
+            toggle_placing(ui, &mut self.placing, Placing::Add, "Add by picking");
+        });
+        if self.placing.is_some() {
+            ui.label("Shift-click on the surface to place the object.");
+        }
+        changed
+    }
+
+    // Where each object's centre is, on the surface or in the chart.
+    pub fn centres(&self, space: &impl Space) -> Vec<Option<Vec3>> {
+        self.list
+            .iter()
+            .map(|object| space.place(object.centre))
+            .collect()
+    }
+
+    // The first object along path, given the objects' centres.
+    pub fn first_hit(
+        &self,
+        space: &impl Space,
+        centres: &[Option<Vec3>],
+        path: &[Vec3],
+    ) -> Option<Hit> {
+        first_hit(path, &self.list, centres, |a, b| space.local_dist(a, b))
+    }
+
+    // The objects' outlines, as the ends of geodesics of their radius
+    // from their centres, or nothing if they're hidden. Tracing needs
+    // the space, so this is separate from drawing them.
+    pub fn outlines(&self, space: &impl Space) -> Vec<Vec<Option<Vec3>>> {
+        const OUTLINE_POINTS: usize = 48;
+
+        if !self.show {
+            return Vec::new();
+        }
+        self.list
+            .iter()
+            .map(|object| {
+                (0..OUTLINE_POINTS)
+                    .map(|idx| {
+                        let angle = 360.0 * idx as f64 / OUTLINE_POINTS as f64;
+                        space
+                            .ray_from(object.centre, angle, object.radius)
+                            .and_then(|path| path.last().cloned())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Draw the outlines found by `outlines`.
+    pub fn rebuild_outlines(&mut self, gl: &Context, outlines: &[Vec<Option<Vec3>>]) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (object, ends) in self.list.iter().zip(outlines.iter()) {
+            for idx in 0..ends.len() {
+                let next = (idx + 1) % ends.len();
+                if let (Some(a), Some(b)) = (&ends[idx], &ends[next]) {
+                    for p in [a, b] {
+                        indices.push((vertices.len() / 6) as u32);
+                        p.push_to(&mut vertices);
+                        vertices.extend(object.colour);
+                    }
+                }
+            }
+        }
+        self.outlines.rebuild(gl, &vertices, &indices);
+    }
+
+    // If stopping rays at objects, cut the fan's rays short at the
+    // first object each hits, returning which that was.
+    pub fn hit(&self, space: &impl Space, fan: &mut [Option<Vec<Vec3>>]) -> Vec<Option<usize>> {
+        if !self.stop_at {
+            return vec![None; fan.len()];
+        }
+        let centres = self.centres(space);
+        fan.iter_mut()
+            .map(|path| {
+                let path = path.as_mut()?;
+                let hit = self.first_hit(space, &centres, path)?;
+                path.truncate(hit.index + 1);
+                Some(hit.object)
+            })
+            .collect()
+    }
+
+    // Draw the rays that hit objects, in the objects' colours, and
+    // count the images of each object. Objects with more than one
+    // image are drawn brighter, with a marker where each image's
+    // middle ray hits.
+    pub fn rebuild_rays(
+        &mut self,
+        gl: &Context,
+        fans: &[Vec<Option<Vec<Vec3>>>],
+        hits: &[Vec<Option<usize>>],
+    ) {
+        // How dark to draw rays to objects seen only once.
+        const SINGLE_SHADE: f32 = 0.5;
+
+        let mut fan_images = Vec::new();
+        for hits in hits.iter() {
+            fan_images.push(images(hits, false));
+        }
+        self.images = vec![0; self.list.len()];
+        for images in fan_images.iter() {
+            let counts = image_counts(images, self.list.len());
+            for (total, count) in self.images.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut add_line = |a: &Vec3, b: &Vec3, c: [f32; 3]| {
+            for p in [a, b] {
+                indices.push((vertices.len() / 6) as u32);
+                p.push_to(&mut vertices);
+                vertices.extend(c);
+            }
+        };
+        for (fan, images) in fans.iter().zip(fan_images.iter()) {
+            for image in images.iter() {
+                let lensed = self.images[image.object] > 1;
+                let colour = self.list[image.object].colour;
+                let shade = if lensed {
+                    colour
+                } else {
+                    colour.map(|c| c * SINGLE_SHADE)
+                };
+                for &idx in image.rays.iter() {
+                    for pair in fan[idx].iter().flat_map(|path| path.windows(2)) {
+                        add_line(&pair[0], &pair[1], shade);
+                    }
+                }
+                let middle = &fan[image.rays[image.rays.len() / 2]];
+                if let Some(p) = middle
+                    .as_ref()
+                    .and_then(|path| path.last())
+                    .filter(|_| lensed)
+                {
+                    for line in shape::marker(p) {
+                        add_line(&line[0], &line[1], colour);
+                    }
+                }
+            }
+        }
+        self.rays.rebuild(gl, &vertices, &indices);
+    }
+
+    pub fn close(&self, gl: &Context) {
+        self.outlines.close(gl);
+        self.rays.close(gl);
+    }
+}
+
+// A button that arms placing, or disarms it if already armed.
+fn toggle_placing(ui: &mut egui::Ui, placing: &mut Option<Placing>, target: Placing, label: &str) {
+    let armed = *placing == Some(target);
+    if ui.selectable_label(armed, label).clicked() {
+        *placing = (!armed).then_some(target);
+    }
+}
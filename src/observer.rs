@@ -0,0 +1,149 @@
+//
+// observer.rs: What an inhabitant of the space sees. They stand at the
+// ray origin, facing along the ray direction, and see the first object
+// along each ray across their field of view. Being 2D, their view is a
+// 1D strip of colour, with the left of their view on the left.
+//
+// Lensing shows up as an object appearing more than once in the strip,
+// e.g. once either side of a bump between it and the observer.
+//
+
+use crate::objects::{self, Objects, Space};
+
+// What the observer sees: The colour along each ray, and which object
+// each ray hit first.
+#[derive(Default)]
+pub struct Sight {
+    strip: Vec<[f32; 3]>,
+    hits: Vec<Option<usize>>,
+}
+
+pub struct Observer {
+    show: bool,
+    // Field of view, in degrees, and the number of rays across it.
+    fov: f64,
+    rays: usize,
+    sight: Sight,
+}
+
+impl Default for Observer {
+    fn default() -> Self {
+        Observer {
+            show: false,
+            fov: 120.0,
+            rays: 400,
+            sight: Sight::default(),
+        }
+    }
+}
+
+impl Observer {
+    // The controls for the observer view, and the view itself: A
+    // strip with the observer's left on the left, and a bar over the
+    // objects seen more than once. Returns whether the view needs
+    // tracing again.
+    pub fn ui(&mut self, ui: &mut egui::Ui, objects: &Objects) -> bool {
+        const STRIP_HEIGHT: f32 = 32.0;
+
+        let mut changed = ui.checkbox(&mut self.show, "Show").changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.fov, 10.0..=360.0).text("Field of view"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.rays, 50..=2000).text("Rays"))
+            .changed();
+        let strip = &self.sight.strip;
+        if !self.show || strip.is_empty() {
+            return changed;
+        }
+
+        let size = egui::vec2(ui.available_width(), STRIP_HEIGHT);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let column = rect.width() / strip.len() as f32;
+        let x_range = |idx: usize| {
+            let left = rect.left() + idx as f32 * column;
+            egui::Rangef::new(left, left + column + 0.5)
+        };
+        for (idx, c) in strip.iter().enumerate() {
+            painter.rect_filled(
+                egui::Rect::from_x_y_ranges(x_range(idx), rect.y_range()),
+                0.0,
+                egui::Rgba::from_rgb(c[0], c[1], c[2]),
+            );
+        }
+        let images = objects::images(&self.sight.hits, self.fov >= 360.0);
+        let counts = objects::image_counts(&images, objects.count());
+        let bar = egui::Rangef::new(rect.top(), rect.top() + 3.0);
+        for image in images.iter().filter(|image| counts[image.object] > 1) {
+            for &idx in image.rays.iter() {
+                painter.rect_filled(
+                    egui::Rect::from_x_y_ranges(x_range(idx), bar),
+                    0.0,
+                    egui::Color32::WHITE,
+                );
+            }
+        }
+        // Mark straight ahead.
+        painter.vline(
+            rect.center().x,
+            egui::Rangef::new(rect.bottom() - 4.0, rect.bottom()),
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        );
+        changed
+    }
+
+    // Trace the observer's rays from origin, centred on direction dir
+    // (in degrees), and find the colour seen along each: That of the
+    // first object hit, darkening with distance. Tracing needs the
+    // space, so this is separate from updating the view.
+    pub fn look(
+        &self,
+        space: &impl Space,
+        objects: &Objects,
+        origin: (f64, f64),
+        dir: f64,
+        max_len: f64,
+    ) -> Sight {
+        // Distance at which objects are drawn at half brightness.
+        const FADE_LEN: f64 = 2.0;
+        // Colours for rays that hit nothing, and for rays that
+        // couldn't be traced at all.
+        const BACKGROUND: [f32; 3] = [0.15, 0.15, 0.15];
+        const NOTHING: [f32; 3] = [0.0, 0.0, 0.0];
+
+        let mut sight = Sight::default();
+        if !self.show {
+            return sight;
+        }
+        let centres = objects.centres(space);
+        for idx in 0..self.rays {
+            let fract = (idx as f64 + 0.5) / self.rays as f64;
+            let angle = dir + (fract - 0.5) * self.fov;
+            let Some(path) = space.ray_from(origin, angle, max_len) else {
+                sight.strip.push(NOTHING);
+                sight.hits.push(None);
+                continue;
+            };
+            let hit = objects.first_hit(space, &centres, &path);
+            sight.hits.push(hit.map(|hit| hit.object));
+            let colour = match hit {
+                Some(hit) => {
+                    let len = path[..=hit.index]
+                        .windows(2)
+                        .map(|pair| space.local_dist(&pair[0], &pair[1]))
+                        .sum::<f64>();
+                    let fade = (1.0 / (1.0 + len / FADE_LEN)) as f32;
+                    objects.colour(hit.object).map(|c| c * fade)
+                }
+                None => BACKGROUND,
+            };
+            sight.strip.push(colour);
+        }
+        sight
+    }
+
+    pub fn set_sight(&mut self, sight: Sight) {
+        self.sight = sight;
+    }
+}
@@ -0,0 +1,85 @@
+//
+// plot.rs: A minimal line graph drawn with egui's painter, to avoid
+// pulling in a plotting crate.
+//
+
+use egui::{Color32, Pos2, Sense, Shape, Stroke, Vec2};
+
+pub struct Series<'a> {
+    pub label: &'a str,
+    pub colour: Color32,
+    pub points: &'a [(f64, f64)],
+}
+
+// Draw the series on shared axes, scaled to fit them all, with a
+// legend underneath.
+pub fn line_plot(ui: &mut egui::Ui, height: f32, series: &[Series]) {
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(ui.available_width(), height), Sense::hover());
+    let rect = response.rect;
+    painter.rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::GRAY));
+
+    let all = series.iter().flat_map(|s| s.points.iter());
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in all.filter(|(x, y)| x.is_finite() && y.is_finite()) {
+        x_min = x_min.min(*x);
+        x_max = x_max.max(*x);
+        y_min = y_min.min(*y);
+        y_max = y_max.max(*y);
+    }
+    if x_max <= x_min || y_max <= y_min {
+        return;
+    }
+
+    let inner = rect.shrink(4.0);
+    let to_screen = |(x, y): (f64, f64)| {
+        Pos2::new(
+            inner.left() + ((x - x_min) / (x_max - x_min)) as f32 * inner.width(),
+            inner.bottom() - ((y - y_min) / (y_max - y_min)) as f32 * inner.height(),
+        )
+    };
+
+    // The y = 0 axis, if it's not the bottom edge.
+    if y_min < 0.0 {
+        painter.line_segment(
+            [to_screen((x_min, 0.0)), to_screen((x_max, 0.0))],
+            Stroke::new(1.0, Color32::DARK_GRAY),
+        );
+    }
+    for s in series.iter() {
+        let points = s
+            .points
+            .iter()
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .map(|p| to_screen(*p))
+            .collect::<Vec<_>>();
+        painter.add(Shape::line(points, Stroke::new(1.5, s.colour)));
+    }
+
+    // Axis ranges in the corners.
+    let font = egui::FontId::monospace(10.0);
+    let corner = |pos: Pos2, align: egui::Align2, text: String| {
+        painter.text(pos, align, text, font.clone(), Color32::GRAY);
+    };
+    corner(
+        inner.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("{:.3}", y_max),
+    );
+    corner(
+        inner.left_bottom(),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{:.3}", y_min),
+    );
+    corner(
+        inner.right_bottom(),
+        egui::Align2::RIGHT_BOTTOM,
+        format!("s = {:.3}", x_max),
+    );
+
+    ui.horizontal(|ui| {
+        for s in series.iter() {
+            ui.colored_label(s.colour, s.label);
+        }
+    });
+}
@@ -0,0 +1,99 @@
+//
+// polygon.rs: Geodesic polygons, for checking the Gauss-Bonnet
+// theorem.
+//
+// For a polygon whose edges are geodesics, Gauss-Bonnet says that the
+// angle excess (the sum of the interior angles, minus what it would
+// be in the plane) equals the integral of the Gaussian curvature over
+// the enclosed area. It's also the angle a vector turns through when
+// parallel-transported around the boundary.
+//
+// Polygons are described by vertices in X/Y, and the surface is
+// treated as a height field over them.
+//
+
+use crate::transport;
+use crate::vec3::*;
+
+// Twice the signed area of a polygon in the plane: Positive if it's
+// anticlockwise.
+pub fn signed_area(poly: &[(f64, f64)]) -> f64 {
+    (0..poly.len())
+        .map(|idx| {
+            let (x0, y0) = poly[idx];
+            let (x1, y1) = poly[(idx + 1) % poly.len()];
+            x0 * y1 - x1 * y0
+        })
+        .sum()
+}
+
+// Whether a point is inside a polygon in the plane, by counting edge
+// crossings of a ray in the +X direction.
+pub fn contains(poly: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    let mut inside = false;
+    for idx in 0..poly.len() {
+        let (x0, y0) = poly[idx];
+        let (x1, y1) = poly[(idx + 1) % poly.len()];
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// The interior angles of a polygon whose edges are given as paths,
+// with edges[i] running from vertex i to vertex i + 1. The polygon
+// must run anticlockwise looking down `up`, which gives a normal at a
+// point on the surface, consistently oriented.
+pub fn interior_angles(edges: &[Vec<Vec3>], up: impl Fn(&Vec3) -> Vec3) -> Vec<f64> {
+    let count = edges.len();
+    (0..count)
+        .map(|idx| {
+            let outgoing = &edges[idx];
+            let incoming = &edges[(idx + count - 1) % count];
+            let p = &outgoing[0];
+            let n = up(p);
+            let tangent = |v: Vec3| v.sub(&n.scale(v.dot(&n)));
+            // Directions leaving the vertex along each edge.
+            let ahead = tangent(away_from(p, outgoing.iter()));
+            let behind = tangent(away_from(p, incoming.iter().rev()));
+            // Going anticlockwise, the interior is on the left, so it
+            // is swept by turning anticlockwise from ahead to behind.
+            let angle = transport::signed_angle(&ahead, &behind, &n);
+            if angle <= 0.0 {
+                angle + 2.0 * std::f64::consts::PI
+            } else {
+                angle
+            }
+        })
+        .collect()
+}
+
+// The direction from p to the first of the points that's not too
+// close to it. Paths found by shooting end with a very short segment
+// onto the target point, which would give a poor direction.
+fn away_from<'a>(p: &Vec3, mut points: impl Iterator<Item = &'a Vec3>) -> Vec3 {
+    const MIN_DIST: f64 = 1.0e-3;
+    points
+        .find(|q| q.sub(p).len() > MIN_DIST)
+        .unwrap_or(p)
+        .sub(p)
+}
+
+// The angle through which a vector turns when transported once around
+// the polygon, anticlockwise looking down `up`.
+pub fn holonomy(edges: &[Vec<Vec3>], up: impl Fn(&Vec3) -> Vec3) -> Option<f64> {
+    let mut boundary: Vec<Vec3> = Vec::new();
+    for edge in edges.iter() {
+        // Each edge starts where the last one finished.
+        let skip = usize::from(!boundary.is_empty());
+        boundary.extend(edge.iter().skip(skip).cloned());
+    }
+    if boundary.len() < 2 {
+        return None;
+    }
+    let v = boundary[1].sub(&boundary[0]);
+    let vs = transport::transport(&boundary, &v, &up);
+    let n = up(&boundary[0]);
+    Some(transport::signed_angle(&v, &vs[vs.len() - 1], &n))
+}
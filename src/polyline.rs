@@ -0,0 +1,127 @@
+//
+// polyline.rs: Utilities for paths represented as a sequence of
+// points, such as those produced by the tracer.
+//
+
+use crate::vec3::*;
+
+// Cumulative arc length at each point.
+pub fn arc_lengths(points: &[Vec3]) -> Vec<f64> {
+    let mut lens = Vec::with_capacity(points.len());
+    let mut total = 0.0;
+    for (idx, p) in points.iter().enumerate() {
+        if idx > 0 {
+            total += p.sub(&points[idx - 1]).len();
+        }
+        lens.push(total);
+    }
+    lens
+}
+
+// The point at arc length s along the path, interpolating linearly
+// between points, given the result of `arc_lengths`. Clamps to the
+// ends of the path.
+pub fn point_at(points: &[Vec3], lens: &[f64], s: f64) -> Vec3 {
+    let idx = lens.partition_point(|l| *l < s);
+    if idx == 0 {
+        return points[0].clone();
+    }
+    if idx >= points.len() {
+        return points[points.len() - 1].clone();
+    }
+    let (l0, l1) = (lens[idx - 1], lens[idx]);
+    let t = if l1 > l0 { (s - l0) / (l1 - l0) } else { 0.0 };
+    let (p0, p1) = (&points[idx - 1], &points[idx]);
+    p0.add(&p1.sub(p0).scale(t))
+}
+
+// How far apart two paths from the same start get: The maximum
+// distance between points at equal arc length (over the length they
+// have in common), and the distance between their end points.
+pub fn separation(a: &[Vec3], b: &[Vec3]) -> Option<(f64, f64)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (lens_a, lens_b) = (arc_lengths(a), arc_lengths(b));
+    let common = lens_a[a.len() - 1].min(lens_b[b.len() - 1]);
+
+    // Sample at the finer of the two paths' resolutions.
+    let samples = a.len().max(b.len()).max(2);
+    let max_sep = (0..samples)
+        .map(|idx| {
+            let s = common * idx as f64 / (samples - 1) as f64;
+            point_at(a, &lens_a, s).sub(&point_at(b, &lens_b, s)).len()
+        })
+        .fold(0.0, f64::max);
+    let end_sep = a[a.len() - 1].sub(&b[b.len() - 1]).len();
+    Some((max_sep, end_sep))
+}
+
+pub struct Closest {
+    pub point: Vec3,
+    // Index of the segment the point is on (i.e. the point lies
+    // between points[segment] and points[segment + 1]).
+    pub segment: usize,
+    // Arc length along the path to the point.
+    pub arc_len: f64,
+    pub dist: f64,
+}
+
+// The point on the path closest to q.
+pub fn closest_point(points: &[Vec3], q: &Vec3) -> Option<Closest> {
+    let first = points.first()?;
+    let mut best = Closest {
+        point: first.clone(),
+        segment: 0,
+        arc_len: 0.0,
+        dist: first.sub(q).len(),
+    };
+    let mut len = 0.0;
+    for (idx, pair) in points.windows(2).enumerate() {
+        let seg = pair[1].sub(&pair[0]);
+        let seg_len = seg.len();
+        let t = if seg_len > 0.0 {
+            (q.sub(&pair[0]).dot(&seg) / (seg_len * seg_len)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let point = pair[0].add(&seg.scale(t));
+        let dist = point.sub(q).len();
+        if dist < best.dist {
+            best = Closest {
+                point,
+                segment: idx,
+                arc_len: len + t * seg_len,
+                dist,
+            };
+        }
+        len += seg_len;
+    }
+    Some(best)
+}
+
+// Join a sequence of points, such as one per ray in a fan, into
+// paths. A path is broken where a point is missing, or where
+// consecutive points are further apart than max_gap. Single points
+// are dropped.
+pub fn join(points: &[Option<Vec3>], max_gap: f64) -> Vec<Vec<Vec3>> {
+    let mut paths = Vec::new();
+    let mut path: Vec<Vec3> = Vec::new();
+    for p in points.iter() {
+        match p {
+            Some(p) if path.last().is_none_or(|q| q.sub(p).len() < max_gap) => {
+                path.push(p.clone());
+            }
+            next => {
+                if path.len() > 1 {
+                    paths.push(path);
+                }
+                path = next.iter().cloned().collect();
+            }
+        }
+    }
+    if path.len() > 1 {
+        paths.push(path);
+    }
+    paths
+}
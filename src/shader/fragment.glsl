@@ -1,8 +1,27 @@
 precision mediump float;
 
+// If set, shade with Blinn-Phong lighting using the normal.
+uniform bool lit;
+
 in vec3 base_color;
+in vec3 view_normal;
 
 out vec4 color;
 void main() {
-    color = vec4(base_color, 1.0);
+    if (!lit) {
+        color = vec4(base_color, 1.0);
+        return;
+    }
+
+    // The camera looks along -Z. Light both sides of the surface, by
+    // flipping normals that face away.
+    vec3 to_eye = vec3(0.0, 0.0, 1.0);
+    vec3 n = normalize(view_normal);
+    if (dot(n, to_eye) < 0.0) {
+        n = -n;
+    }
+    vec3 to_light = normalize(vec3(-0.4, 0.6, 1.0));
+    float diffuse = max(dot(n, to_light), 0.0);
+    float specular = pow(max(dot(n, normalize(to_light + to_eye)), 0.0), 32.0);
+    color = vec4(base_color * (0.25 + 0.75 * diffuse) + vec3(0.3 * specular), 1.0);
 }
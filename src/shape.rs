@@ -0,0 +1,129 @@
+//
+// shape.rs: Representation of something to be drawn in OpenGL with a
+// single `draw_elements` call.
+//
+
+use glow::{Context, *};
+
+use crate::vec3::*;
+
+pub struct Shape {
+    vao: VertexArray,
+    vbo: Buffer,
+    ibo: Buffer,
+    num_elts: i32,
+    // Number of 3-vectors per vertex: Position, then optionally
+    // colour, then optionally normal. Attribute locations follow the
+    // same order.
+    attribs: u32,
+}
+
+impl Shape {
+    pub fn new(gl: &Context) -> Shape {
+        Shape::with_layout(gl, 1)
+    }
+
+    // A shape with per-vertex colours, drawn with the "vertex_color"
+    // uniform set.
+    pub fn new_colored(gl: &Context) -> Shape {
+        Shape::with_layout(gl, 2)
+    }
+
+    // A shape with per-vertex colours and normals, which can also be
+    // drawn with the "lit" uniform set.
+    pub fn new_lit(gl: &Context) -> Shape {
+        Shape::with_layout(gl, 3)
+    }
+
+    // Create vertex and index buffers, and vertex array to describe vertex buffer.
+    pub fn with_layout(gl: &Context, attribs: u32) -> Shape {
+        unsafe {
+            // We construct buffer, data will be uploaded later.
+            let ibo = gl.create_buffer().unwrap();
+            let vbo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+
+            // We now construct a vertex array to describe the format of the input buffer
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+            let vec_size = core::mem::size_of::<f32>() as i32 * 3;
+            for idx in 0..attribs {
+                gl.vertex_attrib_pointer_f32(
+                    idx,
+                    3,
+                    glow::FLOAT,
+                    false,
+                    vec_size * attribs as i32,
+                    vec_size * idx as i32,
+                );
+            }
+
+            Shape {
+                vbo,
+                vao,
+                ibo,
+                num_elts: 0,
+                attribs,
+            }
+        }
+    }
+
+    pub fn rebuild(&mut self, gl: &Context, vertices: &[f32], indices: &[u32]) {
+        unsafe {
+            let vertices_u8: &[u8] = core::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                std::mem::size_of_val(vertices),
+            );
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_u8, glow::STATIC_DRAW);
+
+            let indices_u8: &[u8] = core::slice::from_raw_parts(
+                indices.as_ptr() as *const u8,
+                std::mem::size_of_val(indices),
+            );
+            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ibo));
+            gl.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, indices_u8, glow::STATIC_DRAW);
+
+            self.num_elts = indices.len() as i32;
+        }
+    }
+
+    pub fn draw(&self, gl: &Context, gl_type: u32) {
+        // Assumes program, uniforms, etc. are set.
+        unsafe {
+            gl.bind_vertex_array(Some(self.vao));
+            for idx in 0..self.attribs {
+                gl.enable_vertex_attrib_array(idx);
+            }
+            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ibo));
+            gl.draw_elements(gl_type, self.num_elts, glow::UNSIGNED_INT, 0);
+            for idx in 0..self.attribs {
+                gl.disable_vertex_attrib_array(idx);
+            }
+        }
+    }
+
+    pub fn close(&self, gl: &Context) {
+        unsafe {
+            gl.delete_vertex_array(self.vao);
+            gl.delete_buffer(self.vbo);
+            gl.delete_buffer(self.ibo);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// Glyphs, as lists of polylines.
+//
+
+// A small cross, to mark a point.
+pub fn marker(p: &Vec3) -> Vec<Vec<Vec3>> {
+    const SIZE: f64 = 0.03;
+    [(SIZE, 0.0, 0.0), (0.0, SIZE, 0.0), (0.0, 0.0, SIZE)]
+        .into_iter()
+        .map(|(x, y, z)| {
+            let d = Vec3 { x, y, z };
+            vec![p.sub(&d), p.add(&d)]
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+//
+// stepper.rs: The ways of taking a step along a geodesic on an
+// implicit surface, shared by the tracer's surfaces in 3D and
+// hyper.rs's spaces in 4D.
+//
+
+use crate::geodesic::{self, Equation, State};
+use crate::implicit::{self, Implicit};
+use crate::vector::Vector;
+
+// How to take a step along a path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stepper {
+    // Extrapolate in a straight line, then move to the nearest point
+    // on the surface, i.e. project along the normal at the new point.
+    NearestPoint,
+    // Extrapolate in a straight line, then project back onto the
+    // surface along the normal at the old point.
+    OldNormal,
+    // Integrate the geodesic equation with fixed-step RK4.
+    Rk4,
+    // Integrate the geodesic equation with adaptive Dormand-Prince.
+    DormandPrince,
+    // Symplectic Stormer-Verlet for a free particle constrained to
+    // the surface (the RATTLE scheme).
+    Verlet,
+}
+
+impl Stepper {
+    pub const ALL: [Stepper; 5] = [
+        Stepper::NearestPoint,
+        Stepper::OldNormal,
+        Stepper::Rk4,
+        Stepper::DormandPrince,
+        Stepper::Verlet,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stepper::NearestPoint => "Nearest point projection",
+            Stepper::OldNormal => "Old normal projection",
+            Stepper::Rk4 => "RK4 geodesic",
+            Stepper::DormandPrince => "Dormand-Prince geodesic",
+            Stepper::Verlet => "Stormer-Verlet",
+        }
+    }
+
+    // Whether this stepper integrates an ODE, and so may benefit from
+    // re-projection onto the surface.
+    pub fn is_integrator(&self) -> bool {
+        matches!(self, Stepper::Rk4 | Stepper::DormandPrince)
+    }
+
+    // The stepper used in a chart, where only the geodesic equation
+    // integrators make sense: Dormand-Prince is used as itself, and
+    // anything else as RK4.
+    pub fn in_chart(self) -> Stepper {
+        if self == Stepper::DormandPrince {
+            self
+        } else {
+            Stepper::Rk4
+        }
+    }
+
+    // Choose a stepper. In a chart, only offer those that work there,
+    // and show the one actually used.
+    pub fn combo(ui: &mut egui::Ui, label: &str, stepper: &mut Stepper, chart: bool) -> bool {
+        let shown = if chart { stepper.in_chart() } else { *stepper };
+        egui::ComboBox::from_label(label)
+            .selected_text(shown.label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for s in Stepper::ALL
+                    .into_iter()
+                    .filter(|s| !chart || s.in_chart() == *s)
+                {
+                    changed |= ui.selectable_value(stepper, s, s.label()).changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false)
+    }
+}
+
+// Take a single step of step_len along a path with the given stepper.
+// With reproject, the integrators' results are pulled back onto the
+// surface afterwards.
+pub fn advance<V: Vector, F: Implicit<V> + Equation<V> + ?Sized>(
+    f: &F,
+    stepper: Stepper,
+    reproject: bool,
+    s: &State<V>,
+    step_len: f64,
+    adaptive: &mut geodesic::Adaptive,
+) -> Option<State<V>> {
+    let next = match stepper {
+        Stepper::NearestPoint => {
+            let p = implicit::step_nearest(f, &s.p, &s.v.scale(step_len))?;
+            let v = p.sub(&s.p).norm();
+            return Some(State { p, v });
+        }
+        Stepper::OldNormal => {
+            let delta = s.v.scale(step_len);
+            let norm = f.gradient(&s.p).norm();
+            let p = implicit::step(f, &s.p, &delta, &norm)?;
+            let v = p.sub(&s.p).norm();
+            return Some(State { p, v });
+        }
+        Stepper::Verlet => {
+            // Position update: A constrained drift, with the
+            // constraint force along the old normal (SHAKE). No
+            // retrying with smaller steps, as that would throw off
+            // the velocity.
+            let delta = s.v.scale(step_len);
+            let norm = f.gradient(&s.p).norm();
+            let p = implicit::intersect_line(f, &s.p.add(&delta), &norm)?;
+            // Velocity update: The implied velocity, with a constraint
+            // force along the new normal to make it tangent (RATTLE).
+            // Not renormalised, so that speed is only conserved as
+            // well as the integrator manages.
+            let v = p.sub(&s.p).scale(step_len.recip());
+            let v = tangent_part(f, &p, &v);
+            return Some(State { p, v });
+        }
+        Stepper::Rk4 => geodesic::rk4_step(f, s, step_len),
+        Stepper::DormandPrince => adaptive.step(f, s)?,
+    };
+
+    if !reproject {
+        return Some(next);
+    }
+    // Errors in the integration let the path drift off the surface.
+    // Pull it back along the normal, and make the direction tangent
+    // again.
+    let norm = f.gradient(&next.p).norm();
+    let p = implicit::intersect_line(f, &next.p, &norm).unwrap_or(next.p);
+    let v = tangent_part(f, &p, &next.v).norm();
+    Some(State { p, v })
+}
+
+// The part of v in the tangent space at p.
+pub fn tangent_part<V: Vector>(f: &(impl Implicit<V> + ?Sized), p: &V, v: &V) -> V {
+    let norm = f.gradient(p).norm();
+    v.sub(&norm.scale(v.dot(&norm)))
+}
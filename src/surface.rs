@@ -0,0 +1,343 @@
+//
+// surface.rs: The implicit surfaces that can be traced over. A surface
+// is the set of points where `Surface::value` is zero, and the tracer
+// only ever talks to surfaces through the `Surface` trait, so new
+// ones can be registered without touching the tracer.
+//
+
+use crate::dual::{self, Scalar};
+use crate::expr::Expression;
+use crate::mat3::*;
+use crate::vec3::*;
+
+use std::f64::consts::PI;
+
+////////////////////////////////////////////////////////////////////////
+// Domain: The region of the XY plane over which the surface is
+// drawn and rays are traced.
+//
+
+#[derive(Clone, Debug)]
+pub struct Domain {
+    pub x_min: f64,
+    pub x_max: f64,
+    pub y_min: f64,
+    pub y_max: f64,
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Domain {
+            x_min: -1.0,
+            x_max: 1.0,
+            y_min: -1.0,
+            y_max: 1.0,
+        }
+    }
+}
+
+impl Domain {
+    pub fn contains(&self, p: &Vec3) -> bool {
+        self.x_min <= p.x && p.x <= self.x_max && self.y_min <= p.y && p.y <= self.y_max
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// The Surface trait.
+//
+// Coordinates passed in are before Z scaling: The tracer divides z by
+// its z_scale before evaluating the surface, so that every surface
+// gets flattened or exaggerated the same way.
+//
+
+pub trait Surface {
+    // Name shown in the UI.
+    fn label(&self) -> &str;
+
+    // The implicit function, zero on the surface.
+    fn value(&self, p: &Vec3) -> f64;
+
+    // Exact gradient of `value`, if the surface can supply it. If
+    // not, the tracer uses finite differences.
+    fn gradient(&self, _p: &Vec3) -> Option<Vec3> {
+        None
+    }
+
+    // Exact Hessian (matrix of second derivatives) of `value`, if the
+    // surface can supply it. If not, the tracer uses finite
+    // differences.
+    fn hessian(&self, _p: &Vec3) -> Option<Mat3> {
+        None
+    }
+
+    // Region of the XY plane to draw and trace over.
+    fn domain(&self) -> Domain {
+        Domain::default()
+    }
+
+    // Whether there is a second sheet, found by projecting upwards
+    // from below rather than downwards from above, which needs its
+    // own grid.
+    fn has_second_sheet(&self) -> bool {
+        false
+    }
+
+    // If the surface folds back, multiple solutions get too close
+    // together when flattened and the solver has a bad time. This
+    // puts a floor on the absolute z_scale used.
+    fn min_z_scale(&self) -> f64 {
+        0.0
+    }
+
+    // Any surface-specific controls. Returns true if the surface
+    // changed.
+    fn ui(&mut self, _ui: &mut egui::Ui) -> bool {
+        false
+    }
+}
+
+// The surfaces that come with the program, in the order they appear
+// in the UI.
+pub fn builtin_surfaces() -> Vec<Box<dyn Surface>> {
+    vec![
+        Box::new(Plane),
+        Box::new(PosCurve),
+        Box::new(NegCurve),
+        Box::new(SinXLin),
+        Box::new(SinXQuad),
+        Box::new(Hole),
+        Box::new(ExprSurface::new("x*x + y*y - z*z - a", &[0.1])),
+    ]
+}
+
+////////////////////////////////////////////////////////////////////////
+// Built-in surfaces.
+//
+
+// Implements `value`, `gradient` and `hessian` for a type with a
+// method `eval<S: Scalar>(&self, x: S, y: S, z: S) -> S`.
+macro_rules! autodiff_surface {
+    () => {
+        fn value(&self, p: &Vec3) -> f64 {
+            self.eval(p.x, p.y, p.z)
+        }
+
+        fn gradient(&self, p: &Vec3) -> Option<Vec3> {
+            Some(dual::gradient(p, |x, y, z| self.eval(x, y, z)))
+        }
+
+        fn hessian(&self, p: &Vec3) -> Option<Mat3> {
+            Some(dual::hessian(p, |x, y, z| self.eval(x, y, z)))
+        }
+    };
+}
+
+pub struct Plane;
+
+impl Plane {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        (x + y) * 0.5 - z
+    }
+}
+
+impl Surface for Plane {
+    fn label(&self) -> &str {
+        "Plane"
+    }
+
+    autodiff_surface!();
+}
+
+pub struct PosCurve;
+
+impl PosCurve {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        -(x * x + y * y) * 0.5 - z
+    }
+}
+
+impl Surface for PosCurve {
+    fn label(&self) -> &str {
+        "Positive curvature"
+    }
+
+    autodiff_surface!();
+}
+
+pub struct NegCurve;
+
+impl NegCurve {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        (x * x - y * y) * 0.5 - z
+    }
+}
+
+impl Surface for NegCurve {
+    fn label(&self) -> &str {
+        "Negative curvature"
+    }
+
+    autodiff_surface!();
+}
+
+pub struct SinXLin;
+
+impl SinXLin {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        (y * (4.0 * PI)).sin() * x - z
+    }
+}
+
+impl Surface for SinXLin {
+    fn label(&self) -> &str {
+        "Sin x Linear"
+    }
+
+    autodiff_surface!();
+}
+
+pub struct SinXQuad;
+
+impl SinXQuad {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        (y * (4.0 * PI)).sin() * x * x - z
+    }
+}
+
+impl Surface for SinXQuad {
+    fn label(&self) -> &str {
+        "Sin x Quad"
+    }
+
+    autodiff_surface!();
+}
+
+pub struct Hole;
+
+impl Hole {
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        x * x + y * y - z * z - 0.1
+    }
+}
+
+impl Surface for Hole {
+    fn label(&self) -> &str {
+        "Wormhole"
+    }
+
+    autodiff_surface!();
+
+    fn has_second_sheet(&self) -> bool {
+        true
+    }
+
+    fn min_z_scale(&self) -> f64 {
+        0.02
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// ExprSurface: A user-defined surface, typed into the UI using the
+// expression language from expr.rs.
+//
+
+pub struct ExprSurface {
+    src: String,
+    // Last expression that parsed successfully.
+    expr: Option<Expression>,
+    error: Option<String>,
+    // Values for the expression's named parameters, in order.
+    params: Vec<f64>,
+    second_sheet: bool,
+}
+
+impl ExprSurface {
+    pub fn new(src: &str, params: &[f64]) -> ExprSurface {
+        let mut surface = ExprSurface {
+            src: String::new(),
+            expr: None,
+            error: None,
+            params: Vec::new(),
+            second_sheet: false,
+        };
+        surface.set_src(src);
+        for (value, init) in surface.params.iter_mut().zip(params.iter()) {
+            *value = *init;
+        }
+        surface
+    }
+
+    fn eval<S: Scalar>(&self, x: S, y: S, z: S) -> S {
+        // With no valid expression, fall back to a plane.
+        self.expr
+            .as_ref()
+            .map_or(-z, |expr| expr.eval(x, y, z, &self.params))
+    }
+
+    // Parse a new expression. On failure, the error is recorded for
+    // display and the last good expression is kept. Returns true if
+    // the expression was replaced.
+    fn set_src(&mut self, src: &str) -> bool {
+        self.src = src.to_string();
+        match Expression::parse(src) {
+            Ok(expr) => {
+                // Keep the values of parameters that survive the edit.
+                let old_names = self.expr.as_ref().map_or(&[][..], |e| e.params());
+                self.params = expr
+                    .params()
+                    .iter()
+                    .map(|name| {
+                        old_names
+                            .iter()
+                            .position(|old_name| old_name == name)
+                            .map_or(0.0, |idx| self.params[idx])
+                    })
+                    .collect();
+                self.expr = Some(expr);
+                self.error = None;
+                true
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                false
+            }
+        }
+    }
+}
+
+impl Surface for ExprSurface {
+    fn label(&self) -> &str {
+        "Custom expression"
+    }
+
+    autodiff_surface!();
+
+    fn has_second_sheet(&self) -> bool {
+        self.second_sheet
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut src = self.src.clone();
+        if ui
+            .add(egui::TextEdit::singleline(&mut src).hint_text("f(x, y, z) = 0"))
+            .changed()
+        {
+            changed |= self.set_src(&src);
+        }
+        if let Some(err) = &self.error {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+        if let Some(expr) = &self.expr {
+            for (name, value) in expr.params().iter().zip(self.params.iter_mut()) {
+                changed |= ui
+                    .add(egui::Slider::new(value, -2.0..=2.0).text(name.as_str()))
+                    .changed();
+            }
+        }
+        changed |= ui
+            .checkbox(&mut self.second_sheet, "Second sheet")
+            .changed();
+        changed
+    }
+}
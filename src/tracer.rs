@@ -4,160 +4,460 @@
 // f(x, y, z) = 0.
 //
 
-use glow::{Context, *};
-
+use glow::Context;
+
+use crate::bvp;
+use crate::colour;
+use crate::curvature;
+use crate::cutlocus;
+use crate::fmm;
+use crate::geodesic::{self, State};
+use crate::hyper::HyperView;
+use crate::implicit;
+use crate::jacobi;
+use crate::mat3::*;
+use crate::mesh::*;
+use crate::mesher;
+use crate::metric::{self, Metric};
+use crate::objects::{self, Objects};
+use crate::observer::Observer;
+use crate::plot;
+use crate::polygon;
+use crate::polyline;
+use crate::shape::{self, Shape};
+use crate::stepper::{self, Stepper};
+use crate::surface::*;
+use crate::transport;
 use crate::vec3::*;
+use crate::wormhole::WormholeView;
+
+// The surface shown at startup, by label.
+const DEFAULT_SURFACE: &str = "Sin x Quad";
 
 // Size of a step when tracing a ray.
 const RAY_STEP: f64 = 0.01;
 
-// Step size when doing finite-difference calculations.
+// Largest step the adaptive integrator may take, to keep the drawn
+// paths smooth.
+const MAX_ADAPTIVE_STEP: f64 = 0.05;
+
+// The widest the fan of rays can be, in degrees.
+const MAX_RAY_WIDTH: f64 = 90.0;
+
+// Give up on paths longer than this many points, as e.g. a ray
+// circling the wormhole's throat may never leave the domain.
+const MAX_PATH_POINTS: usize = 10_000;
+
+// Convergence tolerance for the surface solver.
 const EPSILON: f64 = 1.0e-7;
 
+// Step size for central differences of the gradient, for surfaces
+// that don't supply an exact Hessian. Second derivatives are
+// differences of differences, so need a larger step than the gradient
+// to keep rounding error down.
+const HESSIAN_EPSILON: f64 = 1.0e-4;
+
 ////////////////////////////////////////////////////////////////////////
-// Shape: Representation of something to be drawn in OpenGL with a
-// single `draw_elements` call.
-//
+// The core path tracer.
 
-pub struct Shape {
-    vao: VertexArray,
-    vbo: Buffer,
-    ibo: Buffer,
-    num_elts: i32,
+// Parallel transport around a closed loop.
+#[derive(Clone, Debug)]
+struct Holonomy {
+    // Rotation of the transported vector, in radians.
+    angle: f64,
+    // Integral of Gaussian curvature over the enclosed region, which
+    // the angle should match (modulo 2 pi).
+    total_curvature: Option<f64>,
 }
 
-impl Shape {
-    // Create vertex and index buffers, and vertex array to describe vertex buffer.
-    fn new(gl: &Context) -> Shape {
-        unsafe {
-            // We construct buffer, data will be uploaded later.
-            let ibo = gl.create_buffer().unwrap();
-            let vbo = gl.create_buffer().unwrap();
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-
-            // We now construct a vertex array to describe the format of the input buffer
-            let vao = gl.create_vertex_array().unwrap();
-            gl.bind_vertex_array(Some(vao));
-            gl.vertex_attrib_pointer_f32(
-                0,
-                3,
-                glow::FLOAT,
-                false,
-                core::mem::size_of::<f32>() as i32 * 3,
-                0,
-            );
+// Separation of the central ray from its neighbour in the fan, as
+// (arc length, separation) pairs.
+#[derive(Clone, Debug)]
+struct Deviation {
+    // Angle between the two rays at the origin, in radians.
+    dtheta: f64,
+    // From the Jacobi equation along the central ray.
+    predicted: Vec<(f64, f64)>,
+    // Distance between points at equal arc length along the rays.
+    measured: Vec<(f64, f64)>,
+    // Arc length of the first conjugate point, if any.
+    conjugate: Option<f64>,
+}
 
-            Shape {
-                vbo,
-                vao,
-                ibo,
-                num_elts: 0,
-            }
+// An end of the two-point geodesic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum End {
+    A,
+    B,
+}
+
+// How well a geodesic polygon obeys Gauss-Bonnet. The angle excess,
+// the holonomy and the total curvature should all agree.
+#[derive(Clone, Debug)]
+struct PolygonReport {
+    // Interior angles, in radians, going anticlockwise.
+    angles: Vec<f64>,
+    // Sum of the interior angles, less (n - 2) pi.
+    excess: f64,
+    holonomy: Option<f64>,
+    area: Option<f64>,
+    total_curvature: Option<f64>,
+}
+
+// What to colour the grid by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GridColouring {
+    Plain,
+    Gaussian,
+    Mean,
+}
+
+impl GridColouring {
+    const ALL: [GridColouring; 3] = [
+        GridColouring::Plain,
+        GridColouring::Gaussian,
+        GridColouring::Mean,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            GridColouring::Plain => "Plain",
+            GridColouring::Gaussian => "Gaussian curvature",
+            GridColouring::Mean => "Mean curvature",
         }
     }
+}
 
-    fn rebuild(&mut self, gl: &Context, vertices: &[f32], indices: &[u32]) {
-        unsafe {
-            let vertices_u8: &[u8] = core::slice::from_raw_parts(
-                vertices.as_ptr() as *const u8,
-                std::mem::size_of_val(vertices),
-            );
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_u8, glow::STATIC_DRAW);
+// How to draw the surface itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SurfaceStyle {
+    Wireframe,
+    Shaded,
+    Both,
+}
 
-            let indices_u8: &[u8] = core::slice::from_raw_parts(
-                indices.as_ptr() as *const u8,
-                std::mem::size_of_val(indices),
-            );
-            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ibo));
-            gl.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, indices_u8, glow::STATIC_DRAW);
+impl SurfaceStyle {
+    const ALL: [SurfaceStyle; 3] = [
+        SurfaceStyle::Wireframe,
+        SurfaceStyle::Shaded,
+        SurfaceStyle::Both,
+    ];
 
-            self.num_elts = indices.len() as i32;
+    fn label(&self) -> &'static str {
+        match self {
+            SurfaceStyle::Wireframe => "Wireframe",
+            SurfaceStyle::Shaded => "Shaded",
+            SurfaceStyle::Both => "Both",
         }
     }
 
-    pub fn draw(&self, gl: &Context, gl_type: u32) {
-        // Assumes program, uniforms, etc. are set.
-        unsafe {
-            gl.bind_vertex_array(Some(self.vao));
-            gl.enable_vertex_attrib_array(0);
-            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ibo));
-            gl.draw_elements(gl_type, self.num_elts, glow::UNSIGNED_INT, 0);
-            gl.disable_vertex_attrib_array(0);
-        }
+    pub fn wireframe(&self) -> bool {
+        matches!(self, SurfaceStyle::Wireframe | SurfaceStyle::Both)
     }
 
-    fn close(&self, gl: &Context) {
-        unsafe {
-            gl.delete_vertex_array(self.vao);
-            gl.delete_buffer(self.vbo);
-            gl.delete_buffer(self.ibo);
-        }
+    pub fn shaded(&self) -> bool {
+        matches!(self, SurfaceStyle::Shaded | SurfaceStyle::Both)
     }
 }
 
-////////////////////////////////////////////////////////////////////////
-// The core path tracer.
-
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
-pub enum Function {
-    Plane,
-    PosCurve,
-    NegCurve,
-    SinXLin,
-    SinXQuad,
-    Hole,
+// How to mesh the surface for shading, distance fields and export.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MeshMethod {
+    // Project vertically onto the surface from a grid over the
+    // domain. Fast, but only handles surfaces that are (one or two)
+    // height fields.
+    HeightField,
+    // Marching tetrahedra over the box containing the domain. Handles
+    // any surface, and joins sheets up (e.g. through the wormhole's
+    // throat).
+    MarchingTetrahedra,
 }
 
-impl Function {
+impl MeshMethod {
+    const ALL: [MeshMethod; 2] = [MeshMethod::HeightField, MeshMethod::MarchingTetrahedra];
+
     fn label(&self) -> &'static str {
         match self {
-            Function::Plane => "Plane",
-            Function::PosCurve => "Positive curvature",
-            Function::NegCurve => "Negative curvature",
-            Function::SinXLin => "Sin x Linear",
-            Function::SinXQuad => "Sin x Quad",
-            Function::Hole => "Wormhole",
+            MeshMethod::HeightField => "Height field",
+            MeshMethod::MarchingTetrahedra => "Marching tetrahedra",
+        }
+    }
+
+    // The largest mesh size that's quick enough to rebuild while
+    // dragging sliders. Marching tetrahedra samples a 3D grid, so its
+    // cost grows with the cube of the size.
+    fn max_size(&self) -> usize {
+        match self {
+            MeshMethod::HeightField => 200,
+            MeshMethod::MarchingTetrahedra => 64,
         }
     }
 }
 
+// Colour of the shaded surface, when it's not colour-mapped.
+const SURFACE_COLOUR: [f32; 3] = [0.45, 0.55, 0.7];
+
+// How far the fans traced by two steppers differ.
+#[derive(Clone, Debug)]
+struct Divergence {
+    // Largest distance between corresponding points on any pair of
+    // rays, at equal arc length.
+    max_separation: f64,
+    // Largest distance between the end points of any pair of rays.
+    max_end_distance: f64,
+}
+
 pub struct Tracer {
     pub grid: Shape,
+    // Filled surface, for shaded rendering.
+    pub shaded: Shape,
     pub paths: Shape,
     pub paths2: Shape,
+    // The curve through the tips of the rays, when they're limited in
+    // length.
+    pub wavefront: Shape,
+    // The same fan, traced with a second stepper for comparison.
+    pub compare_paths: Shape,
+    // Geodesics between two chosen points.
+    pub bvp_paths: Shape,
+    // Parallel-transported vectors, and the loop they're transported
+    // around.
+    pub transport_glyphs: Shape,
+    pub polygon_paths: Shape,
+    // Conjugate points along the fan's rays, and the caustic.
+    pub conjugate_markers: Shape,
+    pub caustic: Shape,
+    // Geodesics in all directions, coloured by direction, and the
+    // estimated cut locus.
+    pub exp_map: Shape,
+    pub cut_locus: Shape,
+    // Rays through a 3D space embedded in 4D.
+    pub hyper: HyperView,
+    // Objects placed in the space, for rays to hit.
+    pub objects: Objects,
+    // Geodesic distance from the ray origin, as colour and isolines.
+    pub distance_fill: Shape,
+    pub isolines: Shape,
     grid_size: usize,
     z_scale: f64,
     ray_start: (f64, f64),
     ray_dir: f64,
     ray_count: usize,
     ray_width: f64,
+    // While aiming the fan by dragging, the direction the drag started
+    // in, and the last, smallest and largest angles swept from there.
+    aim_sweep: Option<(f64, f64, f64, f64)>,
+    // Stop rays at max_len, rather than when they leave the domain.
+    limit_len: bool,
+    max_len: f64,
+    // Grow the rays over time, anim_len being their current length,
+    // at anim_speed units per second.
+    animate: bool,
+    anim_speed: f64,
+    anim_len: f64,
     origin_ok: bool,
-    func: Function,
+    // Registry of available surfaces, and index of the current one.
+    surfaces: Vec<Box<dyn Surface>>,
+    surface_idx: usize,
+    // Intrinsically-defined spaces, and the index of the one being
+    // explored instead of the surface, if any.
+    metrics: Vec<Box<dyn Metric>>,
+    metric_idx: Option<usize>,
+    // The wormhole raytracer's view.
+    wormhole: WormholeView,
+    // What an inhabitant at the ray origin, facing along ray_dir,
+    // sees.
+    observer: Observer,
+    stepper: Stepper,
+    // Error tolerance per step for the adaptive stepper.
+    tolerance: f64,
+    // Whether to push the geodesic integrators' points back onto the
+    // surface after each step.
+    reproject: bool,
+    compare: bool,
+    compare_stepper: Stepper,
+    divergence: Option<Divergence>,
+    // End points of the two-point geodesic, in X/Y, the one the next
+    // picked point moves, if any, and the geodesics between them.
+    show_bvp: bool,
+    bvp_a: (f64, f64),
+    bvp_b: (f64, f64),
+    bvp_picking: Option<End>,
+    bvp: bvp::Cache,
+    // Transport a frame along the rays, starting at this angle (in
+    // degrees) to each ray.
+    show_transport: bool,
+    transport_angle: f64,
+    // Transport around a loop of this radius (in X/Y) around the ray
+    // origin.
+    show_loop: bool,
+    loop_radius: f64,
+    holonomy: Option<Holonomy>,
+    show_conjugate: bool,
+    show_caustic: bool,
+    // Number of rays in each of the dense fans traced for the
+    // caustic.
+    caustic_rays: usize,
+    show_exp_map: bool,
+    show_cut_locus: bool,
+    // Length of the geodesics in the exponential map view.
+    exp_radius: f64,
+    exp_rays: usize,
+    show_deviation: bool,
+    deviation: Option<Deviation>,
+    // Vertices of the geodesic polygon, in X/Y.
+    show_polygon: bool,
+    polygon: Vec<(f64, f64)>,
+    polygon_report: Option<PolygonReport>,
+    // The geodesics along the polygon's edges, so only edges with a
+    // moved vertex are solved again.
+    polygon_edges: bvp::Cache,
+    show_distance: bool,
+    // Number of cells along each side of the mesh.
+    mesh_size: usize,
+    iso_spacing: f64,
+    mesh: Mesh,
+    grid_colouring: GridColouring,
+    surface_style: SurfaceStyle,
+    mesh_method: MeshMethod,
 }
 
 impl Tracer {
     pub fn new(gl: &Context) -> Tracer {
-        Tracer {
-            grid: Shape::new(gl),
+        let mut tracer = Tracer {
+            grid: Shape::new_colored(gl),
+            shaded: Shape::new_lit(gl),
             paths: Shape::new(gl),
             paths2: Shape::new(gl),
+            wavefront: Shape::new(gl),
+            compare_paths: Shape::new(gl),
+            bvp_paths: Shape::new(gl),
+            transport_glyphs: Shape::new(gl),
+            polygon_paths: Shape::new(gl),
+            conjugate_markers: Shape::new(gl),
+            caustic: Shape::new(gl),
+            exp_map: Shape::new_colored(gl),
+            cut_locus: Shape::new(gl),
+            hyper: HyperView::new(gl),
+            objects: Objects::new(gl),
+            distance_fill: Shape::new_lit(gl),
+            isolines: Shape::new(gl),
             grid_size: 30,
             z_scale: 0.25,
             ray_start: (0.0, -0.9),
             ray_dir: 0.0,
             ray_count: 10,
             ray_width: 30.0,
+            aim_sweep: None,
+            limit_len: false,
+            max_len: 2.0,
+            animate: false,
+            anim_speed: 0.5,
+            anim_len: 0.0,
             origin_ok: true,
-            func: Function::SinXQuad,
+            surfaces: Vec::new(),
+            surface_idx: 0,
+            metrics: metric::builtin_metrics(),
+            metric_idx: None,
+            wormhole: WormholeView::default(),
+            observer: Observer::default(),
+            stepper: Stepper::OldNormal,
+            tolerance: 1.0e-8,
+            reproject: false,
+            compare: false,
+            compare_stepper: Stepper::Rk4,
+            divergence: None,
+            show_bvp: false,
+            bvp_a: (-0.5, -0.5),
+            bvp_b: (0.5, 0.5),
+            bvp_picking: None,
+            bvp: bvp::Cache::default(),
+            show_transport: false,
+            transport_angle: 90.0,
+            show_loop: false,
+            loop_radius: 0.3,
+            holonomy: None,
+            show_conjugate: false,
+            show_caustic: false,
+            caustic_rays: 200,
+            show_exp_map: false,
+            show_cut_locus: false,
+            exp_radius: 1.0,
+            exp_rays: 360,
+            show_deviation: false,
+            deviation: None,
+            show_polygon: false,
+            polygon: vec![(-0.5, -0.4), (0.5, -0.4), (0.0, 0.5)],
+            polygon_report: None,
+            polygon_edges: bvp::Cache::default(),
+            show_distance: false,
+            mesh_size: 80,
+            iso_spacing: 0.1,
+            mesh: Mesh::default(),
+            grid_colouring: GridColouring::Plain,
+            surface_style: SurfaceStyle::Wireframe,
+            mesh_method: MeshMethod::HeightField,
+        };
+        for surface in builtin_surfaces() {
+            tracer.register_surface(surface);
         }
+        tracer.surface_idx = tracer
+            .surfaces
+            .iter()
+            .position(|surface| surface.label() == DEFAULT_SURFACE)
+            .unwrap_or(0);
+        tracer
+    }
+
+    // Add a surface to the list offered in the UI.
+    pub fn register_surface(&mut self, surface: Box<dyn Surface>) {
+        self.surfaces.push(surface);
+    }
+
+    fn surface(&self) -> &dyn Surface {
+        self.surfaces[self.surface_idx].as_ref()
+    }
+
+    // The metric-defined space in use, if we're not on a surface.
+    fn metric(&self) -> Option<&dyn Metric> {
+        self.metric_idx.map(|idx| self.metrics[idx].as_ref())
+    }
+
+    // Whether we're exploring the embedded surface, which everything
+    // other than the rays themselves relies on.
+    fn on_surface(&self) -> bool {
+        self.metric_idx.is_none()
+    }
+
+    // The region rays are traced over, in the surface's X/Y or the
+    // metric's chart.
+    fn domain(&self) -> Domain {
+        match self.metric() {
+            Some(metric) => metric.domain(),
+            None => self.surface().domain(),
+        }
+    }
+
+    pub fn surface_style(&self) -> SurfaceStyle {
+        self.surface_style
     }
 
     pub fn ui(&mut self, ui: &mut egui::Ui, gl: &Context) {
         use egui::Color32;
         let mut needs_regrid = false;
+        // The surface, or how geodesics are traced over it, changed,
+        // so every geodesic needs solving again.
+        let mut needs_resurface = false;
+        let mut needs_retrace = false;
         let mut needs_repath = false;
+        let mut needs_resolve = false;
+        let mut needs_polygon = false;
+        let mut needs_remesh = false;
+        let mut needs_redistance = false;
+        // Only the fans and wavefront change as the rays grow.
+        let mut needs_animate = false;
         // Is there a less unpleasant way to make this type nicely?
+        let domain = self.domain();
         let red_on_fail: &dyn Fn(egui::Slider) -> egui::Slider = &|x| {
             if self.origin_ok {
                 x
@@ -168,17 +468,58 @@ impl Tracer {
         needs_regrid |= ui
             .add(egui::Slider::new(&mut self.grid_size, 2..=100).text("Grid size"))
             .changed();
-        needs_regrid |= ui
+        needs_resurface |= ui
             .add(egui::Slider::new(&mut self.z_scale, -1.0..=1.0).text("Z scale"))
             .changed();
+        let max_mesh_size = self.mesh_method.max_size();
+        needs_remesh |= ui
+            .add(egui::Slider::new(&mut self.mesh_size, 10..=max_mesh_size).text("Mesh size"))
+            .changed();
+        needs_remesh |= egui::ComboBox::from_label("Mesher")
+            .selected_text(self.mesh_method.label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for m in MeshMethod::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.mesh_method, m, m.label())
+                        .changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false);
+        self.mesh_size = self.mesh_size.min(self.mesh_method.max_size());
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("Export OBJ").clicked() {
+            const PATH: &str = "surface.obj";
+            match self.export_obj(PATH) {
+                Ok(()) => log::info!("Wrote mesh to {}", PATH),
+                Err(e) => log::error!("Couldn't write mesh to {}: {}", PATH, e),
+            }
+        }
+        needs_remesh |= egui::ComboBox::from_label("Surface style")
+            .selected_text(self.surface_style.label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for s in SurfaceStyle::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.surface_style, s, s.label())
+                        .changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false);
         needs_repath |= ui
             .add(red_on_fail(
-                egui::Slider::new(&mut self.ray_start.0, -1.0..=1.0).text("X ray origin"),
+                egui::Slider::new(&mut self.ray_start.0, domain.x_min..=domain.x_max)
+                    .text("X ray origin"),
             ))
             .changed();
         needs_repath |= ui
             .add(red_on_fail(
-                egui::Slider::new(&mut self.ray_start.1, -1.0..=1.0).text("Y ray origin"),
+                egui::Slider::new(&mut self.ray_start.1, domain.y_min..=domain.y_max)
+                    .text("Y ray origin"),
             ))
             .changed();
         needs_repath |= ui
@@ -188,36 +529,354 @@ impl Tracer {
             .add(egui::Slider::new(&mut self.ray_count, 1..=30).text("Ray count"))
             .changed();
         needs_repath |= ui
-            .add(egui::Slider::new(&mut self.ray_width, 1.0..=90.0).text("Ray fan width"))
+            .add(egui::Slider::new(&mut self.ray_width, 1.0..=MAX_RAY_WIDTH).text("Ray fan width"))
             .changed();
-        needs_regrid |= egui::ComboBox::from_label("Function")
-            .selected_text(self.func.label())
+        ui.horizontal(|ui| {
+            needs_repath |= ui
+                .checkbox(&mut self.limit_len, "Limit ray length")
+                .changed();
+            needs_repath |= ui
+                .add(egui::Slider::new(&mut self.max_len, 0.1..=10.0).logarithmic(true))
+                .changed();
+        });
+        ui.horizontal(|ui| {
+            needs_repath |= ui.checkbox(&mut self.animate, "Animate").changed();
+            ui.add(
+                egui::Slider::new(&mut self.anim_speed, 0.05..=2.0)
+                    .logarithmic(true)
+                    .text("Speed"),
+            );
+        });
+        if self.animate {
+            // Grow the rays out to max_len, then start again.
+            let dt = ui.input(|i| i.stable_dt) as f64;
+            self.anim_len += self.anim_speed * dt;
+            if self.anim_len > self.max_len {
+                self.anim_len = 0.0;
+            }
+            needs_animate = true;
+            ui.ctx().request_repaint();
+        }
+        let space_label = self.metric().map_or("Embedded surface", |m| m.label());
+        needs_resurface |= egui::ComboBox::from_label("Space")
+            .selected_text(space_label)
             .show_ui(ui, |ui| {
-                [
-                    Function::Plane,
-                    Function::PosCurve,
-                    Function::NegCurve,
-                    Function::SinXLin,
-                    Function::SinXQuad,
-                    Function::Hole,
-                ]
-                .iter()
-                .map(|x| ui.selectable_value(&mut self.func, *x, x.label()).changed())
-                // Force evaluation of whole list.
-                .fold(false, |a, b| a || b)
+                let mut changed = ui
+                    .selectable_value(&mut self.metric_idx, None, "Embedded surface")
+                    .changed();
+                for (idx, metric) in self.metrics.iter().enumerate() {
+                    changed |= ui
+                        .selectable_value(&mut self.metric_idx, Some(idx), metric.label())
+                        .changed();
+                }
+                changed
+            })
+            .inner
+            .unwrap_or(false);
+        if let Some(idx) = self.metric_idx {
+            needs_resurface |= self.metric_ui(ui, idx);
+        } else {
+            needs_resurface |= egui::ComboBox::from_label("Function")
+                .selected_text(self.surface().label())
+                .show_ui(ui, |ui| {
+                    let mut changed = false;
+                    for (idx, surface) in self.surfaces.iter().enumerate() {
+                        changed |= ui
+                            .selectable_value(&mut self.surface_idx, idx, surface.label())
+                            .changed();
+                    }
+                    changed
+                })
+                .inner
+                .unwrap_or(false);
+            needs_resurface |= self.surfaces[self.surface_idx].ui(ui);
+        }
+        needs_regrid |= egui::ComboBox::from_label("Grid colour")
+            .selected_text(self.grid_colouring.label())
+            .show_ui(ui, |ui| {
+                let mut changed = false;
+                for c in GridColouring::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.grid_colouring, c, c.label())
+                        .changed();
+                }
+                changed
             })
             .inner
             .unwrap_or(false);
+        if let Some(metric) = self.metric() {
+            let (x, y) = self.ray_start;
+            if let Some(k) = metric::gaussian_curvature(metric, &Vec3 { x, y, z: 0.0 }) {
+                ui.label(format!("Curvature at origin: K = {:.4}", k));
+            }
+        } else if let Some(p) = self.point_at(self.ray_start) {
+            let c = curvature::at(self, &p);
+            ui.label(format!(
+                "Curvature at origin: K = {:.4}, H = {:.4}",
+                c.gaussian, c.mean
+            ));
+        }
+        let chart = !self.on_surface();
+        needs_retrace |= Stepper::combo(ui, "Stepper", &mut self.stepper, chart);
+        ui.horizontal(|ui| {
+            needs_repath |= ui.checkbox(&mut self.compare, "Compare with").changed();
+            needs_repath |= Stepper::combo(ui, "", &mut self.compare_stepper, chart);
+        });
+        if let Some(d) = &self.divergence {
+            ui.label(format!(
+                "Max separation: {:.3e}, max endpoint distance: {:.3e}",
+                d.max_separation, d.max_end_distance
+            ));
+        }
+        let uses = |s: Stepper| self.stepper == s || (self.compare && self.compare_stepper == s);
+        if uses(Stepper::DormandPrince) {
+            needs_retrace |= ui
+                .add(
+                    egui::Slider::new(&mut self.tolerance, 1.0e-12..=1.0e-3)
+                        .logarithmic(true)
+                        .text("Tolerance"),
+                )
+                .changed();
+        }
+        if Stepper::ALL
+            .into_iter()
+            .any(|s| s.is_integrator() && uses(s))
+        {
+            needs_retrace |= ui
+                .checkbox(&mut self.reproject, "Re-project onto surface")
+                .changed();
+        }
+        egui::CollapsingHeader::new("Two-point geodesic").show(ui, |ui| {
+            needs_resolve |= self.bvp_ui(ui, &domain);
+        });
+        egui::CollapsingHeader::new("Conjugate points").show(ui, |ui| {
+            needs_repath |= ui
+                .checkbox(&mut self.show_conjugate, "Mark on rays")
+                .changed();
+            needs_repath |= ui.checkbox(&mut self.show_caustic, "Caustic").changed();
+            needs_repath |= ui
+                .add(egui::Slider::new(&mut self.caustic_rays, 10..=2000).text("Caustic rays"))
+                .changed();
+        });
+        egui::CollapsingHeader::new("Exponential map").show(ui, |ui| {
+            needs_repath |= ui.checkbox(&mut self.show_exp_map, "Show").changed();
+            needs_repath |= ui.checkbox(&mut self.show_cut_locus, "Cut locus").changed();
+            needs_repath |= ui
+                .add(egui::Slider::new(&mut self.exp_radius, 0.1..=4.0).text("Radius"))
+                .changed();
+            needs_repath |= ui
+                .add(egui::Slider::new(&mut self.exp_rays, 12..=1440).text("Rays"))
+                .changed();
+        });
+        egui::CollapsingHeader::new("3D space in 4D").show(ui, |ui| {
+            needs_repath |= self.hyper.ui(ui);
+        });
+        egui::CollapsingHeader::new("Objects").show(ui, |ui| {
+            let was_placing = self.objects.placing();
+            needs_repath |= self.objects.ui(ui, &domain, self.ray_start);
+            if self.objects.placing() && !was_placing {
+                self.bvp_picking = None;
+            }
+        });
+        egui::CollapsingHeader::new("Observer view").show(ui, |ui| {
+            needs_repath |= self.observer.ui(ui, &self.objects);
+        });
+        egui::CollapsingHeader::new("Wormhole view").show(ui, |ui| {
+            self.wormhole.ui(ui);
+        });
+        egui::CollapsingHeader::new("Geodesic deviation").show(ui, |ui| {
+            needs_repath |= ui.checkbox(&mut self.show_deviation, "Show").changed();
+            if let Some(d) = &self.deviation {
+                ui.label(format!(
+                    "Central ray vs. next ray, {:.3} deg apart",
+                    d.dtheta.to_degrees()
+                ));
+                plot::line_plot(
+                    ui,
+                    150.0,
+                    &[
+                        plot::Series {
+                            label: "Jacobi field",
+                            colour: Color32::YELLOW,
+                            points: &d.predicted,
+                        },
+                        plot::Series {
+                            label: "Measured",
+                            colour: Color32::LIGHT_RED,
+                            points: &d.measured,
+                        },
+                    ],
+                );
+                if let Some(s) = d.conjugate {
+                    ui.label(format!("First conjugate point at s = {:.4}", s));
+                }
+            }
+        });
+        egui::CollapsingHeader::new("Geodesic polygon").show(ui, |ui| {
+            needs_polygon |= self.polygon_ui(ui, &domain);
+        });
+        egui::CollapsingHeader::new("Parallel transport").show(ui, |ui| {
+            needs_repath |= ui
+                .checkbox(&mut self.show_transport, "Along rays")
+                .changed();
+            needs_repath |= ui
+                .add(
+                    egui::Slider::new(&mut self.transport_angle, -180.0..=180.0)
+                        .text("Angle to ray"),
+                )
+                .changed();
+            needs_repath |= ui.checkbox(&mut self.show_loop, "Around loop").changed();
+            needs_repath |= ui
+                .add(egui::Slider::new(&mut self.loop_radius, 0.01..=1.0).text("Loop radius"))
+                .changed();
+            if let Some(h) = &self.holonomy {
+                ui.label(format!("Holonomy: {:.3} deg", h.angle.to_degrees()));
+                if let Some(k) = h.total_curvature {
+                    ui.label(format!("Enclosed curvature: {:.3} deg", k.to_degrees()));
+                }
+            } else if self.show_loop {
+                ui.colored_label(Color32::RED, "Loop is not on the surface");
+            }
+        });
+        egui::CollapsingHeader::new("Distance field").show(ui, |ui| {
+            needs_remesh |= ui.checkbox(&mut self.show_distance, "Show").changed();
+            needs_redistance |= ui
+                .add(
+                    egui::Slider::new(&mut self.iso_spacing, 0.01..=0.5)
+                        .logarithmic(true)
+                        .text("Isoline spacing"),
+                )
+                .changed();
+        });
+        let stale = needs_resurface || needs_retrace;
+        needs_regrid |= needs_resurface;
+        needs_repath |= needs_retrace;
+        if stale {
+            self.bvp = bvp::Cache::default();
+            self.polygon_edges = bvp::Cache::default();
+        }
         if needs_regrid {
             self.regrid(gl);
         }
+        if needs_regrid || needs_remesh {
+            self.remesh(gl);
+        } else if needs_repath || needs_redistance {
+            self.redistance(gl);
+        }
         if needs_regrid || needs_repath {
             self.repath(gl);
+        } else if needs_animate {
+            self.refans(gl);
+        }
+        if stale || needs_resolve {
+            self.resolve(gl);
+        }
+        if stale || needs_polygon {
+            self.repolygon(gl);
         }
     }
 
+    // The metric's own controls. If they switch it to another chart on
+    // the same space, move the ray origin and direction so that the
+    // rays stay the same. Returns whether the metric changed.
+    fn metric_ui(&mut self, ui: &mut egui::Ui, idx: usize) -> bool {
+        let metric = &mut self.metrics[idx];
+        let (x, y) = self.ray_start;
+        let dir = self.ray_dir.to_radians();
+        let p = Vec3 { x, y, z: 0.0 };
+        let ahead = p.add(&Vec3 {
+            x: dir.sin() * RAY_STEP,
+            y: dir.cos() * RAY_STEP,
+            z: 0.0,
+        });
+        let refs = metric
+            .contains(&p)
+            .then(|| (metric.to_reference(&p), metric.to_reference(&ahead)));
+
+        if !metric.ui(ui) {
+            return false;
+        }
+        if let Some((p, ahead)) = refs {
+            let (p, ahead) = (metric.to_chart(&p), metric.to_chart(&ahead));
+            let delta = ahead.sub(&p);
+            if p.x.is_finite() && p.y.is_finite() && delta.len() > 0.0 {
+                self.ray_start = (p.x, p.y);
+                self.ray_dir = delta.x.atan2(delta.y).to_degrees();
+            }
+        }
+        // Points near the edge of one model can map far outside the
+        // next one's domain, so pull the origin back in, falling back
+        // to the middle of the domain.
+        let metric = &self.metrics[idx];
+        let domain = metric.domain();
+        let (x, y) = self.ray_start;
+        let clamped = Vec3 {
+            x: x.clamp(domain.x_min, domain.x_max),
+            y: y.clamp(domain.y_min, domain.y_max),
+            z: 0.0,
+        };
+        self.ray_start = if metric::valid_at(metric.as_ref(), &clamped) {
+            (clamped.x, clamped.y)
+        } else {
+            (
+                0.5 * (domain.x_min + domain.x_max),
+                0.5 * (domain.y_min + domain.y_max),
+            )
+        };
+        true
+    }
+
+    // The controls for the two-point geodesic. Returns whether it
+    // needs solving again.
+    fn bvp_ui(&mut self, ui: &mut egui::Ui, domain: &Domain) -> bool {
+        let mut changed = ui.checkbox(&mut self.show_bvp, "Show").changed();
+        for (end, point) in [(End::A, &mut self.bvp_a), (End::B, &mut self.bvp_b)] {
+            ui.horizontal(|ui| {
+                let armed = self.bvp_picking == Some(end);
+                if ui
+                    .selectable_label(armed, format!("Pick {:?}", end))
+                    .clicked()
+                {
+                    self.bvp_picking = (!armed).then_some(end);
+                    self.objects.stop_placing();
+                }
+                changed |= ui
+                    .add(egui::Slider::new(&mut point.0, domain.x_min..=domain.x_max).text("X"))
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut point.1, domain.y_min..=domain.y_max).text("Y"))
+                    .changed();
+            });
+        }
+        if let Some(end) = self.bvp_picking {
+            ui.label(format!("Shift-click on the surface to place {:?}.", end));
+        }
+        match self.bvp.solutions().next() {
+            None if self.show_bvp => {
+                ui.colored_label(egui::Color32::RED, "A or B is not on the surface");
+            }
+            Some(sol) if sol.geodesics.is_empty() => {
+                ui.colored_label(egui::Color32::RED, "No geodesic found");
+            }
+            Some(sol) => {
+                for (idx, g) in sol.geodesics.iter().enumerate() {
+                    ui.label(format!("Geodesic {}: length {:.4}", idx + 1, g.length));
+                }
+                if sol.geodesics.len() > 1 {
+                    ui.label("Multiple geodesics exist");
+                }
+                if sol.relaxed {
+                    ui.label("(Shooting failed, found by relaxation)");
+                }
+            }
+            None => {}
+        }
+        changed
+    }
+
     // Update the ray origin, used by keyboard input.
     pub fn update_origin(&mut self, gl: &Context, dx: f64, dy: f64, dtheta: f64) {
+        let domain = self.domain();
         let x = &mut self.ray_start.0;
         let y = &mut self.ray_start.1;
         let theta = &mut self.ray_dir;
@@ -232,8 +891,8 @@ impl Tracer {
         let adx = dx * rad.cos() + dy * rad.sin();
         let ady = dx * -rad.sin() + dy * rad.cos();
 
-        *x = (*x + adx).max(-1.0).min(1.0);
-        *y = (*y + ady).max(-1.0).min(1.0);
+        *x = (*x + adx).max(domain.x_min).min(domain.x_max);
+        *y = (*y + ady).max(domain.y_min).min(domain.y_max);
         *theta += dtheta;
         if *theta > 180.0 {
             *theta -= 360.0;
@@ -242,15 +901,986 @@ impl Tracer {
         }
 
         self.repath(gl);
+        self.redistance(gl);
+    }
+
+    // Where the line from eye in direction dir first meets the visible
+    // surface, within max_len, in X/Y. In a chart, that's where it
+    // meets the XY plane.
+    pub fn pick(&self, eye: &Vec3, dir: &Vec3, max_len: f64) -> Option<(f64, f64)> {
+        // March along the line to find where it first crosses the
+        // surface, then home in from just before.
+        const PICK_STEPS: usize = 2000;
+
+        let domain = self.domain();
+        if let Some(metric) = self.metric() {
+            let t = -eye.z / dir.z;
+            let p = eye.add(&dir.scale(t));
+            let ok = t > 0.0 && t <= max_len && domain.contains(&p) && metric::valid_at(metric, &p);
+            return ok.then_some((p.x, p.y));
+        }
+        // Only march over the part of the line above or below the
+        // domain.
+        let (mut t0, mut t1) = (0.0, max_len);
+        for (o, d, lo, hi) in [
+            (eye.x, dir.x, domain.x_min, domain.x_max),
+            (eye.y, dir.y, domain.y_min, domain.y_max),
+        ] {
+            if d.abs() < EPSILON {
+                if o < lo || hi < o {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((lo - o) / d, (hi - o) / d);
+            t0 = f64::max(t0, a.min(b));
+            t1 = f64::min(t1, a.max(b));
+        }
+        if t0 >= t1 {
+            return None;
+        }
+        let step = (t1 - t0) / PICK_STEPS as f64;
+        let mut prev = eye.add(&dir.scale(t0));
+        let mut prev_val = self.dist(&prev);
+        for idx in 1..=PICK_STEPS {
+            let p = eye.add(&dir.scale(t0 + idx as f64 * step));
+            let val = self.dist(&p);
+            if val.signum() != prev_val.signum() {
+                // Homing in may land on the surface outside the
+                // domain, where nothing's drawn, so keep looking.
+                if let Some(hit) = self
+                    .intersect_line(&prev, dir)
+                    .filter(|q| domain.contains(q))
+                {
+                    return Some((hit.x, hit.y));
+                }
+            }
+            prev = p;
+            prev_val = val;
+        }
+        None
+    }
+
+    // Put whatever the UI is waiting to place at a picked point, or
+    // else move the ray origin there. Returns whether it was the ray
+    // origin, which dragging then aims the fan from.
+    pub fn place(&mut self, gl: &Context, p: (f64, f64)) -> bool {
+        if self.objects.place(p) {
+            self.repath(gl);
+            return false;
+        }
+        if let Some(end) = self.bvp_picking.take() {
+            match end {
+                End::A => self.bvp_a = p,
+                End::B => self.bvp_b = p,
+            }
+            self.resolve(gl);
+            return false;
+        }
+        self.set_origin(gl, p);
+        true
+    }
+
+    // Move the ray origin to a picked point, ready to aim the fan from
+    // there.
+    fn set_origin(&mut self, gl: &Context, (x, y): (f64, f64)) {
+        self.ray_start = (x, y);
+        self.aim_sweep = None;
+        self.repath(gl);
+        self.redistance(gl);
+    }
+
+    // Point the fan from the ray origin towards a picked point. The fan
+    // widens to cover the directions swept through since the drag
+    // started, once that's more than its narrowest.
+    pub fn aim(&mut self, gl: &Context, (x, y): (f64, f64)) {
+        let (dx, dy) = (x - self.ray_start.0, y - self.ray_start.1);
+        if dx.hypot(dy) < EPSILON {
+            return;
+        }
+        let angle = dx.atan2(dy).to_degrees();
+        let wrap = |a: f64| (a + 180.0).rem_euclid(360.0) - 180.0;
+        let (start, last, min, max) = self.aim_sweep.unwrap_or((angle, 0.0, 0.0, 0.0));
+        // Unwrap the angle, so sweeping past the back doesn't jump.
+        let swept = last + wrap(angle - start - last);
+        let (min, max) = (min.min(swept), max.max(swept));
+        self.aim_sweep = Some((start, swept, min, max));
+
+        self.ray_dir = wrap(start + (min + max) * 0.5);
+        if max - min >= 1.0 {
+            self.ray_width = (max - min).min(MAX_RAY_WIDTH);
+        }
+        self.repath(gl);
+        self.redistance(gl);
     }
 
     // Regenerate the grid used by OpenGL.
     pub fn regrid(&mut self, gl: &Context) {
         let (vertices, indices) = self.create_grid();
-        self.grid.rebuild(gl, &vertices, &indices);
+        let points = vertices
+            .chunks(3)
+            .map(|v| Vec3 {
+                x: v[0] as f64,
+                y: v[1] as f64,
+                z: v[2] as f64,
+            })
+            .collect::<Vec<_>>();
+        let colours = self.grid_colours(&points);
+        let mut colored = Vec::with_capacity(vertices.len() * 2);
+        for (v, c) in vertices.chunks(3).zip(colours.iter()) {
+            colored.extend(v);
+            colored.extend(c);
+        }
+        self.grid.rebuild(gl, &colored, &indices);
+    }
+
+    // Colours for the grid's vertices.
+    fn grid_colours(&self, points: &[Vec3]) -> Vec<[f32; 3]> {
+        let values = points
+            .iter()
+            .map(|p| match (self.grid_colouring, self.metric()) {
+                (GridColouring::Plain, _) => None,
+                (GridColouring::Gaussian, Some(metric)) => metric::gaussian_curvature(metric, p),
+                // Mean curvature depends on an embedding.
+                (GridColouring::Mean, Some(_)) => None,
+                (GridColouring::Gaussian, None) => Some(curvature::at(self, p).gaussian),
+                (GridColouring::Mean, None) => Some(curvature::at(self, p).mean),
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(values) = values else {
+            return vec![[0.5, 0.5, 0.5]; points.len()];
+        };
+
+        // Scale so that most of the range is used. Curvature can be
+        // extreme in small regions (e.g. the wormhole's throat), so
+        // scale to a high percentile rather than the maximum, and
+        // let the rest saturate.
+        let mut mags = values
+            .iter()
+            .map(|v| v.abs())
+            .filter(|v| v.is_finite())
+            .collect::<Vec<_>>();
+        mags.sort_by(f64::total_cmp);
+        let scale = mags
+            .get(mags.len() * 95 / 100)
+            .copied()
+            .filter(|s| *s > 0.0)
+            .unwrap_or(1.0);
+        values
+            .iter()
+            .map(|v| colour::diverging(v / scale))
+            .collect()
     }
 
     pub fn repath(&mut self, gl: &Context) {
+        let fans = self.refans(gl);
+        self.retransport(gl, &fans);
+        self.recaustic(gl, &fans);
+        self.reexp(gl);
+        self.hyper.rebuild(
+            gl,
+            self.stepper,
+            self.reproject,
+            self.tolerance,
+            self.full_len(),
+        );
+        let outlines = self.objects.outlines(self);
+        self.objects.rebuild_outlines(gl, &outlines);
+        let sight = self.observer.look(
+            self,
+            &self.objects,
+            self.ray_start,
+            self.ray_dir,
+            self.full_len(),
+        );
+        self.observer.set_sight(sight);
+        self.deviation = if self.show_deviation && self.on_surface() {
+            self.deviation()
+        } else {
+            None
+        };
+
+        if !self.compare {
+            self.compare_paths.rebuild(gl, &[], &[]);
+            self.divergence = None;
+            return;
+        }
+
+        let mut compare_fans = self.fan_paths(self.compare_stepper, 0.0);
+        compare_fans.extend(self.fan_paths(self.compare_stepper, 180.0));
+        let traced = compare_fans.iter().flatten().cloned().collect::<Vec<_>>();
+        let (vertices, indices) = self.lines_for(&traced);
+        self.compare_paths.rebuild(gl, &vertices, &indices);
+
+        // Only compare rays both steppers managed to trace.
+        let seps = fans
+            .iter()
+            .flatten()
+            .zip(compare_fans.iter())
+            .filter_map(|(a, b)| polyline::separation(a.as_ref()?, b.as_ref()?))
+            .collect::<Vec<_>>();
+        self.divergence = Some(Divergence {
+            max_separation: seps.iter().map(|s| s.0).fold(0.0, f64::max),
+            max_end_distance: seps.iter().map(|s| s.1).fold(0.0, f64::max),
+        });
+    }
+
+    // Trace and draw the two fans of rays, along with the rays that
+    // hit objects and the wavefront, returning the fans. This is all
+    // that changes as the rays grow when animated.
+    fn refans(&mut self, gl: &Context) -> [Vec<Option<Vec<Vec3>>>; 2] {
+        let mut fans = [
+            self.fan_paths(self.stepper, 0.0),
+            self.fan_paths(self.stepper, 180.0),
+        ];
+        let hits = fans.each_mut().map(|fan| self.objects.hit(self, fan));
+        // Rays that hit objects are drawn in their colours instead.
+        let [missed, missed2] = [0, 1].map(|idx| {
+            fans[idx]
+                .iter()
+                .zip(hits[idx].iter())
+                .filter(|(_, hit)| hit.is_none())
+                .filter_map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        });
+        let (vertices, indices) = self.lines_for(&missed);
+        self.paths.rebuild(gl, &vertices, &indices);
+        let (vertices, indices) = self.lines_for(&missed2);
+        self.paths2.rebuild(gl, &vertices, &indices);
+        self.objects.rebuild_rays(gl, &fans, &hits);
+        self.rewavefront(gl, &fans);
+        fans
+    }
+
+    // Rebuild the filled surface used for shaded rendering.
+    fn reshade(&mut self, gl: &Context) {
+        if !self.surface_style.shaded() {
+            self.shaded.rebuild(gl, &[], &[]);
+            return;
+        }
+        // Share the grid's colouring, to make it easier to read.
+        let colours = if self.grid_colouring == GridColouring::Plain {
+            vec![SURFACE_COLOUR; self.mesh.vertices.len()]
+        } else {
+            self.grid_colours(&self.mesh.vertices)
+        };
+        let (vertices, indices) = self.lit_triangles(&self.mesh, &colours);
+        self.shaded.rebuild(gl, &vertices, &indices);
+    }
+
+    // Convert a mesh into vertices (with colours and normals) and
+    // indices for drawing as lit triangles.
+    fn lit_triangles(&self, mesh: &Mesh, colours: &[[f32; 3]]) -> (Vec<f32>, Vec<u32>) {
+        let mut vertices = Vec::with_capacity(mesh.vertices.len() * 9);
+        for (p, c) in mesh.vertices.iter().zip(colours.iter()) {
+            p.push_to(&mut vertices);
+            vertices.extend(c);
+            self.normal_at(p).norm().push_to(&mut vertices);
+        }
+        let indices = mesh
+            .triangles
+            .iter()
+            .flatten()
+            .map(|idx| *idx as u32)
+            .collect::<Vec<_>>();
+        (vertices, indices)
+    }
+
+    // Transport frames along the rays and around the loop, and draw
+    // them.
+    fn retransport(&mut self, gl: &Context, fans: &[Vec<Option<Vec<Vec3>>>]) {
+        // Draw a frame every this many points.
+        const INTERVAL: usize = 20;
+        const ARROW_LEN: f64 = 0.08;
+
+        let mut glyphs = Vec::new();
+        let mut draw_frames = |tracer: &Tracer, path: &[Vec3], v: &Vec3| {
+            let vs = transport::transport(path, v, |p| tracer.normal_at(p));
+            for (p, v) in path.iter().zip(vs.iter()).step_by(INTERVAL) {
+                // A frame, with the second axis shorter to tell them
+                // apart.
+                let n = tracer.normal_at(p).norm();
+                let e1 = v.norm().scale(ARROW_LEN);
+                let e2 = n.cross(&e1).scale(0.5);
+                glyphs.extend(arrow(p, &e1, &n));
+                glyphs.extend(arrow(p, &e2, &n));
+            }
+        };
+
+        if self.show_transport && self.on_surface() {
+            let angle = self.transport_angle.to_radians();
+            for path in fans
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|path| path.len() >= 2)
+            {
+                // Rotate the initial direction within the tangent
+                // plane.
+                let n = self.normal_at(&path[0]).norm();
+                let t = self.to_tangent(&path[0], &path[1].sub(&path[0]));
+                let v = t.scale(angle.cos()).add(&n.cross(&t).scale(angle.sin()));
+                draw_frames(self, path, &v);
+            }
+        }
+
+        self.holonomy = None;
+        if self.show_loop && self.on_surface() {
+            if let Some(path) = self.origin_loop() {
+                let n = self.normal_at(&path[0]).norm();
+                // Look down on the surface from above.
+                let n = if n.z < 0.0 { n.scale(-1.0) } else { n };
+                let v = self.to_tangent(
+                    &path[0],
+                    &Vec3 {
+                        x: 1.0,
+                        y: 0.0,
+                        z: 0.0,
+                    },
+                );
+                let vs = transport::transport(&path, &v, |p| self.normal_at(p));
+                self.holonomy = Some(Holonomy {
+                    angle: transport::signed_angle(&v, &vs[vs.len() - 1], &n),
+                    total_curvature: self.disc_curvature(),
+                });
+                draw_frames(self, &path, &v);
+                glyphs.push(path);
+            }
+        }
+
+        let (vertices, indices) = self.lines_for(&glyphs);
+        self.transport_glyphs.rebuild(gl, &vertices, &indices);
+    }
+
+    // A loop anticlockwise (seen from above) around the ray origin,
+    // circular in X/Y, starting and ending on the positive X side.
+    fn origin_loop(&self) -> Option<Vec<Vec3>> {
+        const POINTS: usize = 360;
+        let (x0, y0) = self.ray_start;
+        (0..=POINTS)
+            .map(|idx| {
+                let theta = 2.0 * std::f64::consts::PI * idx as f64 / POINTS as f64;
+                let x = x0 + self.loop_radius * theta.cos();
+                let y = y0 + self.loop_radius * theta.sin();
+                self.point_at((x, y))
+            })
+            .collect()
+    }
+
+    // The area of the surface per unit area of X/Y at p, treating the
+    // surface as a height field: |grad f| / |df/dz|.
+    fn area_element(&self, p: &Vec3) -> f64 {
+        let grad = self.normal_at(p);
+        grad.len() / grad.z.abs()
+    }
+
+    // The integral of Gaussian curvature over the surface inside the
+    // loop, by the midpoint rule in polar coordinates.
+    fn disc_curvature(&self) -> Option<f64> {
+        const RINGS: usize = 40;
+        const SECTORS: usize = 72;
+        let (x0, y0) = self.ray_start;
+        let dr = self.loop_radius / RINGS as f64;
+        let dtheta = 2.0 * std::f64::consts::PI / SECTORS as f64;
+        let mut total = 0.0;
+        for ring in 0..RINGS {
+            let r = (ring as f64 + 0.5) * dr;
+            for sector in 0..SECTORS {
+                let theta = (sector as f64 + 0.5) * dtheta;
+                let p = self.point_at((x0 + r * theta.cos(), y0 + r * theta.sin()))?;
+                let area = self.area_element(&p) * r * dr * dtheta;
+                total += curvature::at(self, &p).gaussian * area;
+            }
+        }
+        total.is_finite().then_some(total)
+    }
+
+    // The area of, and integral of Gaussian curvature over, the surface
+    // above the part of the box [x_min, x_max] x [y_min, y_max] where
+    // `inside` holds, by the midpoint rule.
+    fn region_integrals(
+        &self,
+        (x_min, x_max): (f64, f64),
+        (y_min, y_max): (f64, f64),
+        inside: impl Fn((f64, f64)) -> bool,
+    ) -> Option<(f64, f64)> {
+        const SAMPLES: usize = 200;
+        let dx = (x_max - x_min) / SAMPLES as f64;
+        let dy = (y_max - y_min) / SAMPLES as f64;
+        let (mut area, mut total) = (0.0, 0.0);
+        for j in 0..SAMPLES {
+            for i in 0..SAMPLES {
+                let xy = (x_min + (i as f64 + 0.5) * dx, y_min + (j as f64 + 0.5) * dy);
+                if !inside(xy) {
+                    continue;
+                }
+                let p = self.point_at(xy)?;
+                let da = self.area_element(&p) * dx * dy;
+                area += da;
+                total += curvature::at(self, &p).gaussian * da;
+            }
+        }
+        (area.is_finite() && total.is_finite()).then_some((area, total))
+    }
+
+    // Find the edges of the geodesic polygon, work out how well it
+    // obeys Gauss-Bonnet, and draw it.
+    pub fn repolygon(&mut self, gl: &Context) {
+        self.polygon_report = None;
+        let mut edges = None;
+        if self.show_polygon && self.on_surface() {
+            // Everything below assumes the polygon goes anticlockwise.
+            let mut vertices = self.polygon.clone();
+            if polygon::signed_area(&vertices) < 0.0 {
+                vertices.reverse();
+            }
+            let ends = vertices
+                .iter()
+                .map(|v| self.point_at(*v))
+                .collect::<Option<Vec<_>>>()
+                .map(|points| {
+                    let next = points.iter().cycle().skip(1);
+                    points
+                        .iter()
+                        .cloned()
+                        .zip(next.cloned())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            self.polygon_edges = self.polygon_edges.update(self, &ends);
+            edges = self
+                .polygon_edges
+                .solutions()
+                .map(|sol| sol.geodesics.first().map(|g| g.points.clone()))
+                .collect::<Option<Vec<_>>>()
+                .filter(|edges| !edges.is_empty());
+        }
+
+        if let Some(edges) = &edges {
+            // Normals facing up, to match looking down on an
+            // anticlockwise polygon.
+            let up = |p: &Vec3| {
+                let n = self.normal_at(p).norm();
+                if n.z < 0.0 {
+                    n.scale(-1.0)
+                } else {
+                    n
+                }
+            };
+            let angles = polygon::interior_angles(edges, up);
+            let excess =
+                angles.iter().sum::<f64>() - (angles.len() as f64 - 2.0) * std::f64::consts::PI;
+
+            // The region enclosed, following the edges as they curve.
+            let outline = edges
+                .iter()
+                .flatten()
+                .map(|p| (p.x, p.y))
+                .collect::<Vec<_>>();
+            let range = |coord: fn(&(f64, f64)) -> f64| {
+                outline
+                    .iter()
+                    .map(coord)
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), c| {
+                        (lo.min(c), hi.max(c))
+                    })
+            };
+            let integrals = self.region_integrals(range(|p| p.0), range(|p| p.1), |xy| {
+                polygon::contains(&outline, xy)
+            });
+
+            self.polygon_report = Some(PolygonReport {
+                angles,
+                excess,
+                holonomy: polygon::holonomy(edges, up),
+                area: integrals.map(|i| i.0),
+                total_curvature: integrals.map(|i| i.1),
+            });
+        }
+
+        let (vertices, indices) = self.lines_for(edges.as_deref().unwrap_or(&[]));
+        self.polygon_paths.rebuild(gl, &vertices, &indices);
+    }
+
+    // The controls for the geodesic polygon. Returns whether it needs
+    // recalculating.
+    fn polygon_ui(&mut self, ui: &mut egui::Ui, domain: &Domain) -> bool {
+        let mut changed = ui.checkbox(&mut self.show_polygon, "Show").changed();
+        let mut remove = None;
+        let can_remove = self.polygon.len() > 3;
+        for (idx, point) in self.polygon.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}", idx + 1));
+                if ui.button("Pick").clicked() {
+                    *point = self.ray_start;
+                    changed = true;
+                }
+                changed |= ui
+                    .add(egui::Slider::new(&mut point.0, domain.x_min..=domain.x_max).text("X"))
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut point.1, domain.y_min..=domain.y_max).text("Y"))
+                    .changed();
+                if can_remove && ui.button("Remove").clicked() {
+                    remove = Some(idx);
+                }
+            });
+        }
+        if let Some(idx) = remove {
+            self.polygon.remove(idx);
+            changed = true;
+        }
+        if ui.button("Add vertex at ray origin").clicked() {
+            self.polygon.push(self.ray_start);
+            changed = true;
+        }
+
+        match &self.polygon_report {
+            None if self.show_polygon => {
+                ui.colored_label(egui::Color32::RED, "Couldn't find the polygon's edges");
+            }
+            None => {}
+            Some(r) => {
+                let degrees = r
+                    .angles
+                    .iter()
+                    .map(|a| format!("{:.3}", a.to_degrees()))
+                    .collect::<Vec<_>>();
+                ui.label(format!("Interior angles: {} deg", degrees.join(", ")));
+                ui.label(format!(
+                    "Angle sum: {:.4} deg",
+                    r.angles.iter().sum::<f64>().to_degrees()
+                ));
+                ui.label(format!("Angle excess: {:.4} rad", r.excess));
+                if let Some(h) = r.holonomy {
+                    ui.label(format!("Holonomy: {:.4} rad", h));
+                }
+                match (r.area, r.total_curvature) {
+                    (Some(area), Some(k)) => {
+                        ui.label(format!("Area: {:.4}", area));
+                        ui.label(format!("Integral of K dA: {:.4} rad", k));
+                    }
+                    _ => {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            "Couldn't integrate over the enclosed area",
+                        );
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    // Rebuild the surface mesh, and everything drawn from it.
+    pub fn remesh(&mut self, gl: &Context) {
+        self.mesh = if self.on_surface() && (self.surface_style.shaded() || self.show_distance) {
+            self.surface_mesh()
+        } else {
+            Mesh::default()
+        };
+        self.reshade(gl);
+        self.redistance(gl);
+    }
+
+    // Mesh the whole surface with the chosen method.
+    fn surface_mesh(&self) -> Mesh {
+        match self.mesh_method {
+            MeshMethod::HeightField => {
+                let mut mesh = self.height_mesh(1.0);
+                if self.surface().has_second_sheet() {
+                    mesh.append(&self.height_mesh(-1.0));
+                }
+                mesh
+            }
+            MeshMethod::MarchingTetrahedra => {
+                let domain = self.surface().domain();
+                let min = Vec3 {
+                    x: domain.x_min,
+                    y: domain.y_min,
+                    z: -1.0,
+                };
+                let max = Vec3 {
+                    x: domain.x_max,
+                    y: domain.y_max,
+                    z: 1.0,
+                };
+                let mut mesh = mesher::mesh(|p| self.dist(p), &min, &max, self.mesh_size);
+                // The vertices are only linearly interpolated, so
+                // move them onto the surface proper.
+                for p in mesh.vertices.iter_mut() {
+                    let norm = self.normal_at(p).norm();
+                    if let Some(new_p) = self.intersect_line(p, &norm) {
+                        *p = new_p;
+                    }
+                }
+                mesh
+            }
+        }
+    }
+
+    // Write the surface mesh as a Wavefront OBJ file.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_obj(&self, path: &str) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.surface_mesh().write_obj(&mut file)
+    }
+
+    // Recalculate the geodesic distance from the ray origin over the
+    // mesh, and build the coloured surface and isolines showing it.
+    pub fn redistance(&mut self, gl: &Context) {
+        let source = self
+            .point_at(self.ray_start)
+            .filter(|_| self.show_distance && self.on_surface());
+        let (Some(source), false) = (source, self.mesh.vertices.is_empty()) else {
+            self.distance_fill.rebuild(gl, &[], &[]);
+            self.isolines.rebuild(gl, &[], &[]);
+            return;
+        };
+
+        // The source generally isn't a vertex, so start from the
+        // vertices around it, at their straight-line distances.
+        let domain = self.surface().domain();
+        let radius = 2.0 * (domain.x_max - domain.x_min).max(domain.y_max - domain.y_min)
+            / self.mesh_size as f64;
+        let mut sources = self
+            .mesh
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| (idx, p.sub(&source).len()))
+            .filter(|(_, d)| *d <= radius)
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            // Not near the mesh, e.g. on a missing patch. Use the
+            // nearest vertex.
+            sources.extend(
+                self.mesh
+                    .vertices
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, p)| (idx, p.sub(&source).len()))
+                    .min_by(|a, b| a.1.total_cmp(&b.1)),
+            );
+        }
+        let dists = fmm::distances(&self.mesh, &sources);
+
+        let max_dist = dists
+            .iter()
+            .copied()
+            .filter(|d| d.is_finite())
+            .fold(0.0, f64::max);
+        let colours = dists
+            .iter()
+            .map(|d| {
+                if d.is_finite() {
+                    colour::sequential(d / max_dist)
+                } else {
+                    // Unreachable.
+                    [0.3, 0.3, 0.3]
+                }
+            })
+            .collect::<Vec<_>>();
+        let (vertices, indices) = self.lit_triangles(&self.mesh, &colours);
+        self.distance_fill.rebuild(gl, &vertices, &indices);
+
+        let mut lines = Vec::new();
+        let mut level = self.iso_spacing;
+        while level < max_dist {
+            lines.extend(
+                self.mesh
+                    .contour(&dists, level)
+                    .into_iter()
+                    .map(|(a, b)| vec![a, b]),
+            );
+            level += self.iso_spacing;
+        }
+        let (vertices, indices) = self.lines_for(&lines);
+        self.isolines.rebuild(gl, &vertices, &indices);
+    }
+
+    // Sample one sheet of the surface over the domain, as a height
+    // field. The sheet is the one found by projecting vertically from
+    // height sheet_z. Grid cells with a corner where the surface can't
+    // be found are left out.
+    fn height_mesh(&self, sheet_z: f64) -> Mesh {
+        let n = self.mesh_size;
+        let domain = self.surface().domain();
+        let mut mesh = Mesh::default();
+        let mut index = vec![None; (n + 1) * (n + 1)];
+        for j in 0..=n {
+            for i in 0..=n {
+                let x = domain.x_min + (domain.x_max - domain.x_min) * i as f64 / n as f64;
+                let y = domain.y_min + (domain.y_max - domain.y_min) * j as f64 / n as f64;
+                if let Some(p) = self.project_vertical(&Vec3 { x, y, z: sheet_z }) {
+                    index[j * (n + 1) + i] = Some(mesh.vertices.len());
+                    mesh.vertices.push(p);
+                }
+            }
+        }
+        for j in 0..n {
+            for i in 0..n {
+                let corner = |di: usize, dj: usize| index[(j + dj) * (n + 1) + i + di];
+                if let (Some(a), Some(b), Some(c), Some(d)) =
+                    (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1))
+                {
+                    mesh.triangles.push([a, b, c]);
+                    mesh.triangles.push([a, c, d]);
+                }
+            }
+        }
+        mesh
+    }
+
+    // Solve for, and draw, the geodesics between A and B. They're only
+    // solved again if A or B moved since the last time, or the cache
+    // was cleared.
+    pub fn resolve(&mut self, gl: &Context) {
+        let mut ends = Vec::new();
+        if self.show_bvp && self.on_surface() {
+            if let (Some(a), Some(b)) = (self.point_at(self.bvp_a), self.point_at(self.bvp_b)) {
+                ends.push((a, b));
+            }
+        }
+        self.bvp = self.bvp.update(self, &ends);
+
+        let mut paths = Vec::new();
+        if let Some(sol) = self.bvp.solutions().next() {
+            for g in sol.geodesics.iter() {
+                paths.push(g.points.clone());
+            }
+            if let Some(g) = sol.geodesics.first() {
+                // Mark the end points.
+                for p in [&g.points[0], &g.points[g.points.len() - 1]] {
+                    paths.extend(shape::marker(p));
+                }
+            }
+        }
+        let (vertices, indices) = self.lines_for(&paths);
+        self.bvp_paths.rebuild(gl, &vertices, &indices);
+    }
+
+    // The point on the surface above (x, y).
+    fn point_at(&self, (x, y): (f64, f64)) -> Option<Vec3> {
+        self.project_vertical(&Vec3 { x, y, z: 1.0 })
+    }
+
+    // Compare the separation of the central ray and its neighbour in
+    // the fan with the Jacobi field along the central ray.
+    fn deviation(&mut self) -> Option<Deviation> {
+        let step = if self.ray_count > 1 {
+            self.ray_width / (self.ray_count - 1) as f64
+        } else {
+            1.0
+        };
+        let central = self.ray_path(self.stepper, self.ray_dir, f64::INFINITY)?;
+        let neighbour = self.ray_path(self.stepper, self.ray_dir + step, f64::INFINITY)?;
+        if central.len() < 2 || neighbour.len() < 2 {
+            return None;
+        }
+        // The fan's angles are directions in X/Y, so measure the
+        // actual angle between the rays in the tangent plane.
+        let n = self.normal_at(&central[0]);
+        let dtheta = transport::signed_angle(
+            &central[1].sub(&central[0]),
+            &neighbour[1].sub(&neighbour[0]),
+            &n,
+        )
+        .abs();
+
+        let (lens, js) = self.jacobi_along(&central);
+
+        let neighbour_lens = polyline::arc_lengths(&neighbour);
+        let neighbour_len = neighbour_lens[neighbour_lens.len() - 1];
+        let measured = lens
+            .iter()
+            .filter(|s| **s <= neighbour_len)
+            .map(|s| {
+                let p = polyline::point_at(&central, &lens, *s);
+                let q = polyline::point_at(&neighbour, &neighbour_lens, *s);
+                (*s, p.sub(&q).len())
+            })
+            .collect();
+
+        Some(Deviation {
+            dtheta,
+            predicted: lens
+                .iter()
+                .zip(js.iter())
+                .map(|(s, j)| (*s, j * dtheta))
+                .collect(),
+            measured,
+            conjugate: jacobi::conjugate_points(&lens, &js).first().copied(),
+        })
+    }
+
+    // The arc length and Jacobi field at each point along a geodesic.
+    fn jacobi_along(&self, path: &[Vec3]) -> (Vec<f64>, Vec<f64>) {
+        let lens = polyline::arc_lengths(path);
+        let ks = path
+            .iter()
+            .map(|p| curvature::at(self, p).gaussian)
+            .collect::<Vec<_>>();
+        let js = jacobi::integrate(&lens, &ks);
+        (lens, js)
+    }
+
+    // The conjugate points along a geodesic from the ray origin.
+    fn conjugate_points(&self, path: &[Vec3]) -> Vec<Vec3> {
+        let (lens, js) = self.jacobi_along(path);
+        jacobi::conjugate_points(&lens, &js)
+            .into_iter()
+            .map(|s| polyline::point_at(path, &lens, s))
+            .collect()
+    }
+
+    // Mark the conjugate points along the fan's rays, and trace the
+    // caustic.
+    fn recaustic(&mut self, gl: &Context, fans: &[Vec<Option<Vec<Vec3>>>]) {
+        let mut markers = Vec::new();
+        if self.show_conjugate && self.on_surface() {
+            for path in fans.iter().flatten().flatten() {
+                for p in self.conjugate_points(path) {
+                    markers.extend(shape::marker(&p));
+                }
+            }
+        }
+        let (vertices, indices) = self.lines_for(&markers);
+        self.conjugate_markers.rebuild(gl, &vertices, &indices);
+
+        let lines = if self.show_caustic && self.on_surface() {
+            self.caustic_lines()
+        } else {
+            Vec::new()
+        };
+        let (vertices, indices) = self.lines_for(&lines);
+        self.caustic.rebuild(gl, &vertices, &indices);
+    }
+
+    // The caustic is the envelope of the geodesics from the origin,
+    // which is where they have conjugate points. Trace a dense fan
+    // over the same directions as the visible fans, and join up the
+    // n-th conjugate points of neighbouring rays.
+    fn caustic_lines(&mut self) -> Vec<Vec<Vec3>> {
+        // Don't join points further apart than this, as that's a gap
+        // in the caustic, rather than a part of it.
+        const MAX_GAP: f64 = 0.1;
+
+        let mut lines = Vec::new();
+        for offset in [0.0, 180.0] {
+            let start = self.ray_dir + offset - self.ray_width / 2.0;
+            let step = self.ray_width / (self.caustic_rays - 1) as f64;
+            let mut conjugates = Vec::new();
+            for idx in 0..self.caustic_rays {
+                let path = self.ray_path(self.stepper, start + idx as f64 * step, f64::INFINITY);
+                conjugates.push(
+                    path.map(|path| self.conjugate_points(&path))
+                        .unwrap_or_default(),
+                );
+            }
+
+            let most = conjugates.iter().map(|c| c.len()).max().unwrap_or(0);
+            for n in 0..most {
+                let points = conjugates
+                    .iter()
+                    .map(|c| c.get(n).cloned())
+                    .collect::<Vec<_>>();
+                lines.extend(polyline::join(&points, MAX_GAP));
+            }
+        }
+        lines
+    }
+
+    // Trace geodesics in all directions from the ray origin, out to
+    // exp_radius, coloured by their initial direction. The ends of
+    // those reaching full length are joined up into a geodesic circle.
+    // Also estimate the cut locus, where the geodesics stop being
+    // shortest paths.
+    fn reexp(&mut self, gl: &Context) {
+        // How dark to draw the geodesics themselves, relative to the
+        // circle.
+        const RAY_SHADE: f32 = 0.4;
+        // Paths passing further apart than this won't be used to find
+        // the cut locus.
+        const CUT_REACH: f64 = 0.05;
+        // Allow for this much error in the traced lengths.
+        const CUT_SLACK: f64 = 2.0 * RAY_STEP;
+        const MAX_GAP: f64 = 0.1;
+
+        let mut paths = Vec::new();
+        let mut ends = Vec::new();
+        if self.show_exp_map || self.show_cut_locus {
+            for idx in 0..self.exp_rays {
+                let angle = 360.0 * idx as f64 / self.exp_rays as f64;
+                let path = self
+                    .ray_path(self.stepper, angle, self.exp_radius)
+                    .unwrap_or_default();
+                let reached = polyline::arc_lengths(&path)
+                    .last()
+                    .is_some_and(|len| *len >= self.exp_radius - RAY_STEP);
+                ends.push(path.last().filter(|_| reached).cloned());
+                paths.push(path);
+            }
+        }
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        if self.show_exp_map {
+            let colours = (0..self.exp_rays)
+                .map(|idx| colour::cyclic(idx as f64 / self.exp_rays as f64))
+                .collect::<Vec<_>>();
+            let mut add_line = |a: &Vec3, b: &Vec3, c: [f32; 3]| {
+                for p in [a, b] {
+                    indices.push((vertices.len() / 6) as u32);
+                    p.push_to(&mut vertices);
+                    vertices.extend(c);
+                }
+            };
+            for (path, c) in paths.iter().zip(colours.iter()) {
+                let shade = c.map(|x| x * RAY_SHADE);
+                for pair in path.windows(2) {
+                    add_line(&pair[0], &pair[1], shade);
+                }
+            }
+            for idx in 0..ends.len() {
+                let next = (idx + 1) % ends.len();
+                if let (Some(a), Some(b)) = (&ends[idx], &ends[next]) {
+                    if a.sub(b).len() < MAX_GAP {
+                        add_line(a, b, colours[idx]);
+                    }
+                }
+            }
+        }
+        self.exp_map.rebuild(gl, &vertices, &indices);
+
+        // The cut locus needs lengths, which in a chart aren't
+        // Euclidean.
+        let lines = if self.show_cut_locus && self.on_surface() {
+            let cuts = cutlocus::cut_points(&paths, CUT_REACH, CUT_SLACK);
+            polyline::join(&cuts, MAX_GAP)
+        } else {
+            Vec::new()
+        };
+        let (vertices, indices) = self.lines_for(&lines);
+        self.cut_locus.rebuild(gl, &vertices, &indices);
+    }
+
+    // The distance between two nearby points: Euclidean on the
+    // surface, and by the metric in a chart.
+    fn local_dist(&self, a: &Vec3, b: &Vec3) -> f64 {
+        let delta = b.sub(a);
+        match self.metric() {
+            Some(metric) => {
+                let mid = a.add(&delta.scale(0.5));
+                metric::norm_squared(metric, &mid, &delta).max(0.0).sqrt()
+            }
+            None => delta.len(),
+        }
+    }
+
+    // The directions of the rays in the fan, in degrees, rotated by
+    // `offset`.
+    fn fan_angles(&self, offset: f64) -> Vec<f64> {
         let (ray_step, ray_start);
         if self.ray_count > 1 {
             ray_step = self.ray_width / (self.ray_count - 1) as f64;
@@ -259,33 +1889,96 @@ impl Tracer {
             ray_step = 0.0;
             ray_start = self.ray_dir;
         };
+        (0..self.ray_count)
+            .map(|i| ray_start + offset + i as f64 * ray_step)
+            .collect()
+    }
 
-        {
-            let mut vertices = Vec::new();
-            let mut indices = Vec::new();
-            for i in 0..self.ray_count {
-                self.repath_aux(ray_start + i as f64 * ray_step, &mut vertices, &mut indices);
+    // How far to trace the rays in the fans.
+    fn ray_len(&self) -> f64 {
+        if self.animate {
+            self.anim_len
+        } else {
+            self.full_len()
+        }
+    }
+
+    // How far to trace rays that don't grow when animated.
+    fn full_len(&self) -> f64 {
+        if self.limit_len {
+            self.max_len
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    // The wavefront of the fans: A curve through the tips of the rays,
+    // which is part of a geodesic circle around the ray origin. Rays
+    // that left the domain early have no tip to join.
+    fn rewavefront(&mut self, gl: &Context, fans: &[Vec<Option<Vec<Vec3>>>]) {
+        let len = self.ray_len();
+        let mut lines = Vec::new();
+        if len.is_finite() {
+            for fan in fans.iter() {
+                let tips = fan
+                    .iter()
+                    .map(|path| {
+                        let path = path.as_ref()?;
+                        let reached = polyline::arc_lengths(path)
+                            .last()
+                            .is_some_and(|l| *l >= len - RAY_STEP);
+                        path.last().filter(|_| reached).cloned()
+                    })
+                    .collect::<Vec<_>>();
+                lines.extend(polyline::join(&tips, f64::INFINITY));
             }
-            self.paths.rebuild(gl, &vertices, &indices);
         }
-        {
-            let mut vertices = Vec::new();
-            let mut indices = Vec::new();
-            for i in 0..self.ray_count {
-                self.repath_aux(
-                    ray_start + 180.0 + i as f64 * ray_step,
-                    &mut vertices,
-                    &mut indices,
-                );
+        let (vertices, indices) = self.lines_for(&lines);
+        self.wavefront.rebuild(gl, &vertices, &indices);
+    }
+
+    // Trace the fan of rays with the given stepper. Rays that couldn't
+    // be traced are None, so that fans traced with different steppers
+    // line up ray for ray.
+    fn fan_paths(&mut self, stepper: Stepper, offset: f64) -> Vec<Option<Vec<Vec3>>> {
+        self.fan_angles(offset)
+            .into_iter()
+            .map(|angle| self.ray_path(stepper, angle, self.ray_len()))
+            .collect()
+    }
+
+    // Convert paths into vertices and indices for drawing as lines.
+    fn lines_for(&self, paths: &[Vec<Vec3>]) -> (Vec<f32>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for path in paths {
+            let old_len = vertices.len() / 3;
+            for p in path {
+                p.push_to(&mut vertices);
             }
-            self.paths2.rebuild(gl, &vertices, &indices);
+            self.gen_indices(old_len, &vertices, &mut indices);
         }
+        (vertices, indices)
     }
 
     pub fn close(&self, gl: &Context) {
         self.grid.close(gl);
+        self.shaded.close(gl);
         self.paths.close(gl);
         self.paths2.close(gl);
+        self.wavefront.close(gl);
+        self.compare_paths.close(gl);
+        self.bvp_paths.close(gl);
+        self.transport_glyphs.close(gl);
+        self.polygon_paths.close(gl);
+        self.conjugate_markers.close(gl);
+        self.caustic.close(gl);
+        self.exp_map.close(gl);
+        self.cut_locus.close(gl);
+        self.hyper.close(gl);
+        self.objects.close(gl);
+        self.distance_fill.close(gl);
+        self.isolines.close(gl);
     }
 
     // Not a true distance, but the implicit surface function, where
@@ -297,23 +1990,18 @@ impl Tracer {
             return point.z;
         }
 
-        // If the surface folds back, put a floor on the absolute
-        // z_scale, otherwise multiple solutions get too close
-        // together and the solver has a bad time.
-        let mut z_scale = self.z_scale;
-        if self.func == Function::Hole {
-            z_scale = z_scale.signum() * z_scale.abs().max(0.02);
-        }
+        self.surface().value(&Vec3 {
+            z: point.z / self.effective_z_scale(),
+            ..*point
+        })
+    }
 
-        let (x, y, z) = (point.x, point.y, point.z / z_scale);
-        match self.func {
-            Function::Plane => (x + y) * 0.5 - z,
-            Function::PosCurve => -(x * x + y * y) * 0.5 - z,
-            Function::NegCurve => (x * x - y * y) * 0.5 - z,
-            Function::SinXLin => (y * 4.0 * std::f64::consts::PI).sin() * x - z,
-            Function::SinXQuad => (y * 4.0 * std::f64::consts::PI).sin() * x * x - z,
-            Function::Hole => x * x + y * y - z * z - 0.1,
-        }
+    // If the surface folds back, put a floor on the absolute
+    // z_scale, otherwise multiple solutions get too close
+    // together and the solver has a bad time.
+    fn effective_z_scale(&self) -> f64 {
+        let min_z_scale = self.surface().min_z_scale();
+        self.z_scale.signum() * self.z_scale.abs().max(min_z_scale)
     }
 
     // TODO: Need to deal with the cases where the solver fails for
@@ -322,32 +2010,23 @@ impl Tracer {
     // along grid axes.
 
     fn intersect_line(&self, point: &Vec3, direction: &Vec3) -> Option<Vec3> {
-        // Newton-Raphson solver on dist(point + lambda direction)
-        //
-        // In practice, it's locally flat enough that a a single
-        // iteration seems to suffice.
-        const MAX_ITER: usize = 10;
-
-        let mut lambda = 0.0;
-        for _ in 0..MAX_ITER {
-            let guess = point.add(&direction.scale(lambda));
-            let guess_val = self.dist(&guess);
-            if guess_val.abs() < EPSILON {
-                return Some(guess);
-            }
-
-            let guess2 = point.add(&direction.scale(lambda + EPSILON));
-            let guess2_val = self.dist(&guess2);
-
-            let dguess_val = (guess2_val - guess_val) / EPSILON;
+        implicit::intersect_line(self, point, direction)
+    }
 
-            lambda -= guess_val / dguess_val;
+    // If the Z scale is non-degenerate, returns the point in the
+    // surface's own coordinates, along with the Z scale used.
+    fn to_surface_coords(&self, p: &Vec3) -> Option<(Vec3, f64)> {
+        if self.z_scale.abs() <= EPSILON {
+            return None;
         }
-
-        // Could fall back to binary chop, but as it generally seems
-        // to converge in <= 2 iterations if there is a solution, this
-        // seems excessive.
-        None
+        let z_scale = self.effective_z_scale();
+        Some((
+            Vec3 {
+                z: p.z / z_scale,
+                ..*p
+            },
+            z_scale,
+        ))
     }
 
     // Intersect the surface with a line in the z-axis from the
@@ -365,108 +2044,228 @@ impl Tracer {
     // Take a step from p in direction delta, constrained to the
     // surface in direction norm.
     fn step(&self, p: &Vec3, delta: &Vec3, norm: &Vec3) -> Option<Vec3> {
-        let mut delta = delta.clone();
-        // If curvature is extreme, there may be no intersection,
-        // because the normal at p and the normal at the intersection
-        // point are sufficiently different. We try again with a
-        // smaller step.
-        //
-        // An example of extreme curvature is the "wormhole" surface
-        // with z_scale around e.g. 0.01.
-        const MAX_ITER: usize = 8;
-        let mut new_p = None;
-        let mut iter = 0;
-        while new_p.is_none() && iter < MAX_ITER {
-            new_p = self.intersect_line(&p.add(&delta), norm);
-            delta = delta.scale(0.5);
-            iter += 1;
-        }
-        new_p
+        implicit::step(self, p, delta, norm)
     }
 
-    // Calculate a normal vector using finite differences.
+    // Improve a guess at the nearest point on the surface to target.
+    fn refine_nearest(&self, target: &Vec3, guess: Vec3) -> Option<Vec3> {
+        implicit::refine_nearest(self, target, guess)
+    }
+
+    // Calculate a normal vector (the gradient of dist), using the
+    // surface's exact gradient if it supplies one, or central
+    // differences otherwise. The result is not normalised.
     fn normal_at(&self, p: &Vec3) -> Vec3 {
-        let base_dist = self.dist(p);
-        Vec3 {
-            x: self.dist(&Vec3 {
-                x: p.x + EPSILON,
-                ..*p
-            }) - base_dist,
-            y: self.dist(&Vec3 {
-                y: p.y + EPSILON,
-                ..*p
-            }) - base_dist,
-            z: self.dist(&Vec3 {
-                z: p.z + EPSILON,
-                ..*p
-            }) - base_dist,
+        match self.to_surface_coords(p) {
+            None => {
+                // Flat plane, where dist is just z.
+                return Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                };
+            }
+            Some((scaled, z_scale)) => {
+                if let Some(grad) = self.surface().gradient(&scaled) {
+                    // Chain rule for the Z scaling.
+                    return Vec3 {
+                        z: grad.z / z_scale,
+                        ..grad
+                    };
+                }
+            }
         }
+
+        implicit::central_gradient(|q| self.dist(q), p)
+    }
+
+    // Calculate the Hessian of dist, using the surface's exact Hessian
+    // if it supplies one, or central differences of the gradient
+    // otherwise.
+    fn hessian_at(&self, p: &Vec3) -> Mat3 {
+        match self.to_surface_coords(p) {
+            None => return Mat3::zero(),
+            Some((scaled, z_scale)) => {
+                if let Some(mut hessian) = self.surface().hessian(&scaled) {
+                    // Chain rule for the Z scaling, once per
+                    // derivative taken in Z.
+                    for i in 0..3 {
+                        hessian.m[i][2] /= z_scale;
+                        hessian.m[2][i] /= z_scale;
+                    }
+                    return hessian;
+                }
+            }
+        }
+
+        let diff = |dp: Vec3| {
+            self.normal_at(&p.add(&dp))
+                .sub(&self.normal_at(&p.sub(&dp)))
+                .scale(0.5 / HESSIAN_EPSILON)
+        };
+        let cols = [
+            diff(Vec3 {
+                x: HESSIAN_EPSILON,
+                y: 0.0,
+                z: 0.0,
+            }),
+            diff(Vec3 {
+                x: 0.0,
+                y: HESSIAN_EPSILON,
+                z: 0.0,
+            }),
+            diff(Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: HESSIAN_EPSILON,
+            }),
+        ];
+        // Symmetrise, to average out some of the error.
+        let get = |i: usize, j: usize| {
+            let c = &cols[j];
+            [c.x, c.y, c.z][i]
+        };
+        let sym = |i: usize, j: usize| (get(i, j) + get(j, i)) * 0.5;
+        Mat3::symmetric(
+            sym(0, 0),
+            sym(0, 1),
+            sym(0, 2),
+            sym(1, 1),
+            sym(1, 2),
+            sym(2, 2),
+        )
     }
 
     // Clip point back to an edge.
-    fn clip(&self, p: &Vec3, prev: &Vec3) -> Vec3 {
+    fn clip(&self, p: &Vec3, prev: &Vec3) -> Option<Vec3> {
         // Clip last point against grid and add.
+        let domain = self.surface().domain();
         let delta = p.sub(prev);
-        let x_excess = ((p.x.abs()) - 1.0) / delta.x.abs();
-        let y_excess = ((p.y.abs()) - 1.0) / delta.y.abs();
+        let x_excess = (p.x - domain.x_max).max(domain.x_min - p.x) / delta.x.abs();
+        let y_excess = (p.y - domain.y_max).max(domain.y_min - p.y) / delta.y.abs();
         let fract = x_excess.max(y_excess);
-        // We assume we can always find an intersection point at the
-        // grid's edge.
-        self.project_vertical(&p.sub(&delta.scale(fract))).unwrap()
+        // For the built-in surfaces we can always find an
+        // intersection point at the grid's edge, but user-defined
+        // surfaces may not be so well-behaved.
+        self.project_vertical(&p.sub(&delta.scale(fract)))
     }
 
     fn gen_indices(&self, start: usize, vertices: &[f32], indices: &mut Vec<u32>) {
         let len = vertices.len() / 3;
-        for idx in start..len - 2 {
+        for idx in start..len.saturating_sub(1) {
             indices.push(idx as u32);
             indices.push(idx as u32 + 1);
         }
     }
 
-    fn plot_path(
+    // Project a vector into the tangent plane at p, and normalise it.
+    fn to_tangent(&self, p: &Vec3, v: &Vec3) -> Vec3 {
+        let norm = self.normal_at(p).norm();
+        v.sub(&norm.scale(v.dot(&norm))).norm()
+    }
+
+    // Take a single step along a path with the given stepper.
+    fn advance(
         &self,
-        point: &Vec3,
-        prev: &Vec3,
-        vertices: &mut Vec<f32>,
-        indices: &mut Vec<u32>,
-    ) {
-        let old_len = vertices.len() / 3;
+        stepper: Stepper,
+        s: &State<Vec3>,
+        adaptive: &mut geodesic::Adaptive,
+    ) -> Option<State<Vec3>> {
+        stepper::advance(self, stepper, self.reproject, s, RAY_STEP, adaptive)
+    }
 
-        let mut p = point.clone();
-        let mut old_p = prev.clone();
+    // Trace a path from point, heading away from prev, until it
+    // leaves the domain or reaches max_len in length. If it leaves the
+    // domain, the last point is clipped to the domain's edge.
+    fn trace_path(&self, stepper: Stepper, point: &Vec3, prev: &Vec3, max_len: f64) -> Vec<Vec3> {
+        if max_len <= 0.0 {
+            return vec![point.clone()];
+        }
 
-        while p.x.abs() <= 1.0 && p.y.abs() <= 1.0 {
-            p.push_to(vertices);
+        let mut points = Vec::new();
+        let mut len = 0.0;
 
-            let delta = p.sub(&old_p).norm().scale(RAY_STEP);
-            let norm = self.normal_at(&p).norm();
+        let chord = point.sub(prev);
+        let v = if matches!(stepper, Stepper::NearestPoint | Stepper::OldNormal) {
+            chord.norm()
+        } else {
+            // The geodesic equation needs a truly tangent direction.
+            self.to_tangent(point, &chord)
+        };
+        let mut state = State {
+            p: point.clone(),
+            v,
+        };
+        let mut adaptive = geodesic::Adaptive::new(RAY_STEP, MAX_ADAPTIVE_STEP, self.tolerance);
 
-            if let Some(new_p) = self.step(&p, &delta, &norm) {
-                (p, old_p) = (new_p, p);
+        let domain = self.surface().domain();
+        while domain.contains(&state.p) {
+            if points.len() >= MAX_PATH_POINTS {
+                log::warn!("trace_path gave up on a very long path");
+                return points;
+            }
+            points.push(state.p.clone());
+
+            if let Some(next) = self.advance(stepper, &state, &mut adaptive) {
+                let step_len = next.p.sub(&state.p).len();
+                if len + step_len >= max_len {
+                    // Stop exactly at max_len along the last step.
+                    if step_len > 0.0 {
+                        let fract = (max_len - len) / step_len;
+                        points.push(state.p.add(&next.p.sub(&state.p).scale(fract)));
+                    }
+                    return points;
+                }
+                len += step_len;
+                state = next;
             } else {
-                log::error!("plot_path could not extend path");
-                self.gen_indices(old_len, vertices, indices);
-                return;
+                log::error!("trace_path could not extend path");
+                return points;
             }
         }
 
-        self.clip(&p, &old_p).push_to(vertices);
-        self.gen_indices(old_len, vertices, indices);
+        if let Some(p) = points.last().and_then(|prev| self.clip(&state.p, prev)) {
+            points.push(p);
+        }
+        points
     }
 
-    fn repath_aux(&mut self, ray_dir: f64, vertices: &mut Vec<f32>, indices: &mut Vec<u32>) {
-        let (x0, y0) = self.ray_start;
-        let p = if let Some(p) = self.project_vertical(&Vec3 {
+    // Trace the ray from ray_start in direction ray_dir (in degrees),
+    // until it leaves the domain or reaches max_len.
+    fn ray_path(&mut self, stepper: Stepper, ray_dir: f64, max_len: f64) -> Option<Vec<Vec3>> {
+        let path = self.path_from(stepper, self.ray_start, ray_dir, max_len);
+        self.origin_ok = path.is_some();
+        path
+    }
+
+    // Trace a ray from (x0, y0) in direction ray_dir (in degrees),
+    // until it leaves the domain or reaches max_len. None if there's
+    // no surface there to start from.
+    fn path_from(
+        &self,
+        stepper: Stepper,
+        (x0, y0): (f64, f64),
+        ray_dir: f64,
+        max_len: f64,
+    ) -> Option<Vec<Vec3>> {
+        if let Some(metric) = self.metric() {
+            let p = Vec3 {
+                x: x0,
+                y: y0,
+                z: 0.0,
+            };
+            if !metric::valid_at(metric, &p) {
+                return None;
+            }
+            return Some(self.trace_chart_path(stepper, &p, ray_dir, max_len));
+        }
+        // No intersection point at the start, or just behind it for
+        // the initial previous point, means giving up.
+        let p = self.project_vertical(&Vec3 {
             x: x0,
             y: y0,
             z: 1.0,
-        }) {
-            p
-        } else {
-            // No intersection point at ray_start. Give up.
-            self.origin_ok = false;
-            return;
-        };
+        })?;
 
         let ray_dir_rad = ray_dir * std::f64::consts::PI / 180.0;
         let delta = Vec3 {
@@ -476,16 +2275,70 @@ impl Tracer {
         };
 
         // Take a step back, roughly, for initial previous point.
-        let old_p = if let Some(p) = self.project_vertical(&p.sub(&delta)) {
-            p
-        } else {
-            // No intersection point near ray_start. Give up.
-            self.origin_ok = false;
-            return;
-        };
+        let old_p = self.project_vertical(&p.sub(&delta))?;
+
+        Some(self.trace_path(stepper, &p, &old_p, max_len))
+    }
 
-        self.origin_ok = true;
-        self.plot_path(&p, &old_p, vertices, indices);
+    // Trace a geodesic of the metric from point, in the chart
+    // direction ray_dir (in degrees), until it leaves the domain or
+    // the region where the metric is valid, or reaches max_len, using
+    // the stepper's stand-in for charts.
+    fn trace_chart_path(
+        &self,
+        stepper: Stepper,
+        point: &Vec3,
+        ray_dir: f64,
+        max_len: f64,
+    ) -> Vec<Vec3> {
+        let Some(metric) = self.metric() else {
+            return Vec::new();
+        };
+        let eqn = metric::Geodesic(metric);
+        let dir = ray_dir.to_radians();
+        let mut state = State {
+            p: point.clone(),
+            v: metric::unit(
+                metric,
+                point,
+                &Vec3 {
+                    x: dir.sin(),
+                    y: dir.cos(),
+                    z: 0.0,
+                },
+            ),
+        };
+        let mut adaptive = geodesic::Adaptive::new(RAY_STEP, MAX_ADAPTIVE_STEP, self.tolerance);
+
+        let domain = metric.domain();
+        let mut points = vec![state.p.clone()];
+        let mut len = 0.0;
+        while points.len() < MAX_PATH_POINTS {
+            let next = if stepper.in_chart() == Stepper::DormandPrince {
+                adaptive.step(&eqn, &state)
+            } else {
+                Some(geodesic::rk4_step(&eqn, &state, RAY_STEP))
+            };
+            let Some(next) =
+                next.filter(|s| domain.contains(&s.p) && metric::valid_at(metric, &s.p))
+            else {
+                return points;
+            };
+            // The step's length in the metric, measured at its middle.
+            let delta = next.p.sub(&state.p);
+            let mid = state.p.add(&delta.scale(0.5));
+            let step_len = metric::norm_squared(metric, &mid, &delta).sqrt();
+            if len + step_len >= max_len {
+                let fract = (max_len - len) / step_len;
+                points.push(state.p.add(&delta.scale(fract)));
+                return points;
+            }
+            len += step_len;
+            points.push(next.p.clone());
+            state = next;
+        }
+        log::warn!("trace_chart_path gave up on a very long path");
+        points
     }
 
     // This version of plot_path forces the line to lie within a given
@@ -506,7 +2359,8 @@ impl Tracer {
         let mut p = point.clone();
         let mut old_p = prev.clone();
 
-        while p.x.abs() <= 1.0 && p.y.abs() <= 1.0 {
+        let domain = self.surface().domain();
+        while domain.contains(&p) {
             p.push_to(vertices);
 
             let delta = p.sub(&old_p).norm().scale(RAY_STEP);
@@ -526,41 +2380,60 @@ impl Tracer {
             }
         }
 
-        self.clip(&p, &old_p).push_to(vertices);
+        if let Some(p) = self.clip(&p, &old_p) {
+            p.push_to(vertices);
+        }
         self.gen_indices(old_len, vertices, indices);
     }
 
     fn create_grid(&self) -> (Vec<f32>, Vec<u32>) {
+        if let Some(metric) = self.metric() {
+            return self.create_chart_grid(metric);
+        }
         let mut v = Vec::new(); // Vertices
         let mut i = Vec::new(); // Indices
 
+        // The grid is laid out over [-1, 1]^2, then mapped onto the
+        // surface's domain.
+        let domain = self.surface().domain();
+        let to_domain = |p: Vec3| Vec3 {
+            x: (domain.x_min + domain.x_max + p.x * (domain.x_max - domain.x_min)) * 0.5,
+            y: (domain.y_min + domain.y_max + p.y * (domain.y_max - domain.y_min)) * 0.5,
+            z: p.z,
+        };
+
         let mut build = |constraint: &Vec3, flip: f64| {
             let x_scale = constraint.x;
             let y_scale = constraint.y;
             for idx in 0..=self.grid_size {
                 let coord = (idx as f64 / self.grid_size as f64) * 2.0;
-                let p = Vec3 {
-                    x: coord * x_scale - 1.0,
-                    y: coord * y_scale - 1.0,
-                    z: 1.0,
-                }
-                .scale(flip);
-                let p_prev = Vec3 {
-                    x: coord * x_scale - 1.0 - (1.0 - x_scale) * RAY_STEP,
-                    y: coord * y_scale - 1.0 - (1.0 - y_scale) * RAY_STEP,
-                    z: 1.0,
-                }
-                .scale(flip);
-
-                // We assume we can always project_vertical the points
-                // at the grid's edge.
-                self.plot_path_constrained(
-                    &self.project_vertical(&p).unwrap(),
-                    &self.project_vertical(&p_prev).unwrap(),
-                    &mut v,
-                    &mut i,
-                    constraint,
+                let p = to_domain(
+                    Vec3 {
+                        x: coord * x_scale - 1.0,
+                        y: coord * y_scale - 1.0,
+                        z: 1.0,
+                    }
+                    .scale(flip),
+                );
+                let p_prev = to_domain(
+                    Vec3 {
+                        x: coord * x_scale - 1.0 - (1.0 - x_scale) * RAY_STEP,
+                        y: coord * y_scale - 1.0 - (1.0 - y_scale) * RAY_STEP,
+                        z: 1.0,
+                    }
+                    .scale(flip),
                 );
+
+                // The built-in surfaces can always project_vertical the
+                // points at the grid's edge, but a user-defined surface
+                // may not reach the edge at all.
+                if let (Some(p), Some(p_prev)) =
+                    (self.project_vertical(&p), self.project_vertical(&p_prev))
+                {
+                    self.plot_path_constrained(&p, &p_prev, &mut v, &mut i, constraint);
+                } else {
+                    log::warn!("create_grid could not find surface at grid edge");
+                }
             }
         };
 
@@ -581,7 +2454,7 @@ impl Tracer {
             1.0,
         );
         // Fun special case
-        if self.func == Function::Hole {
+        if self.surface().has_second_sheet() {
             build(
                 &Vec3 {
                     x: 1.0,
@@ -602,4 +2475,109 @@ impl Tracer {
 
         (v, i)
     }
+
+    // The grid for a metric: Coordinate lines in the chart, flat in
+    // the XY plane, and missing where the metric isn't valid.
+    fn create_chart_grid(&self, metric: &dyn Metric) -> (Vec<f32>, Vec<u32>) {
+        // Points along each line, enough to show the grid colouring.
+        const LINE_POINTS: usize = 200;
+
+        if let Some(lines) = metric.grid(self.grid_size) {
+            return self.lines_for(&lines);
+        }
+        let domain = metric.domain();
+        let mut lines = Vec::new();
+        for idx in 0..=self.grid_size {
+            let t = idx as f64 / self.grid_size as f64;
+            for vertical in [false, true] {
+                let mut line: Vec<Vec3> = Vec::new();
+                for step in 0..=LINE_POINTS {
+                    let u = step as f64 / LINE_POINTS as f64;
+                    let (s, t) = if vertical { (t, u) } else { (u, t) };
+                    let p = Vec3 {
+                        x: domain.x_min + (domain.x_max - domain.x_min) * s,
+                        y: domain.y_min + (domain.y_max - domain.y_min) * t,
+                        z: 0.0,
+                    };
+                    if metric::valid_at(metric, &p) {
+                        line.push(p);
+                    } else if !line.is_empty() {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                }
+                lines.push(line);
+            }
+        }
+        self.lines_for(&lines)
+    }
+}
+
+impl implicit::Implicit<Vec3> for Tracer {
+    fn value(&self, p: &Vec3) -> f64 {
+        self.dist(p)
+    }
+
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        self.normal_at(p)
+    }
+
+    fn second_along(&self, p: &Vec3, d: &Vec3) -> Option<f64> {
+        Some(self.hessian_at(p).quad_form(d))
+    }
+}
+
+impl geodesic::Field for Tracer {
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        self.normal_at(p)
+    }
+
+    fn hessian(&self, p: &Vec3) -> Mat3 {
+        self.hessian_at(p)
+    }
+}
+
+impl objects::Space for Tracer {
+    fn place(&self, (x, y): (f64, f64)) -> Option<Vec3> {
+        match self.metric() {
+            Some(metric) => Some(Vec3 { x, y, z: 0.0 }).filter(|p| metric::valid_at(metric, p)),
+            None => self.point_at((x, y)),
+        }
+    }
+
+    fn local_dist(&self, a: &Vec3, b: &Vec3) -> f64 {
+        self.local_dist(a, b)
+    }
+
+    fn ray_from(&self, xy: (f64, f64), angle: f64, max_len: f64) -> Option<Vec<Vec3>> {
+        self.path_from(self.stepper, xy, angle, max_len)
+    }
+}
+
+impl bvp::Shooter for Tracer {
+    fn shoot(&self, p: &Vec3, v: &Vec3, max_len: f64) -> Vec<Vec3> {
+        self.trace_path(self.stepper, p, &p.sub(&v.scale(RAY_STEP)), max_len)
+    }
+
+    fn project(&self, p: &Vec3) -> Option<Vec3> {
+        let guess = self
+            .intersect_line(p, &self.normal_at(p).norm())
+            .or_else(|| self.project_vertical(p))?;
+        self.refine_nearest(p, guess)
+    }
+
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        self.normal_at(p)
+    }
+}
+
+// An arrow from p along v, with its head in the plane perpendicular
+// to normal.
+fn arrow(p: &Vec3, v: &Vec3, normal: &Vec3) -> Vec<Vec<Vec3>> {
+    let tip = p.add(v);
+    let back = tip.sub(&v.scale(0.3));
+    let side = normal.cross(v).scale(0.2);
+    vec![
+        vec![p.clone(), tip.clone()],
+        vec![back.add(&side), tip, back.sub(&side)],
+    ]
 }
@@ -0,0 +1,63 @@
+//
+// transport.rs: Parallel transport of tangent vectors along paths on
+// the surface.
+//
+// Parallel transport moves a vector along a path while keeping it
+// tangent to the surface and "not turning" within it. On a curved
+// surface, a vector transported around a closed loop comes back
+// rotated, by an angle equal to the total Gaussian curvature enclosed
+// (the holonomy).
+//
+// Between consecutive points, we rotate the vector by the smallest
+// rotation that takes the old normal to the new one. In the limit of
+// small steps this is exactly the Levi-Civita connection: The vector
+// only ever changes in the normal direction.
+//
+
+use crate::vec3::*;
+
+// Transport v along the path, returning the vector at each point.
+// `normal` gives a (not necessarily unit) normal at a point on the
+// surface.
+pub fn transport(points: &[Vec3], v: &Vec3, normal: impl Fn(&Vec3) -> Vec3) -> Vec<Vec3> {
+    let mut result = Vec::with_capacity(points.len());
+    let Some(first) = points.first() else {
+        return result;
+    };
+    let mut n = normal(first).norm();
+    // Make sure we start tangent.
+    let mut v = v.sub(&n.scale(v.dot(&n)));
+    result.push(v.clone());
+    for p in points[1..].iter() {
+        let mut new_n = normal(p).norm();
+        // The normal's sign is arbitrary, so keep it consistent.
+        if new_n.dot(&n) < 0.0 {
+            new_n = new_n.scale(-1.0);
+        }
+        v = rotate_between(&v, &n, &new_n);
+        n = new_n;
+        result.push(v.clone());
+    }
+    result
+}
+
+// Rotate v by the smallest rotation taking unit vector a to unit
+// vector b (Rodrigues' formula).
+fn rotate_between(v: &Vec3, a: &Vec3, b: &Vec3) -> Vec3 {
+    let axis = a.cross(b);
+    let sin = axis.len();
+    let cos = a.dot(b);
+    if sin < 1.0e-15 {
+        return v.clone();
+    }
+    let k = axis.scale(sin.recip());
+    v.scale(cos)
+        .add(&k.cross(v).scale(sin))
+        .add(&k.scale(k.dot(v) * (1.0 - cos)))
+}
+
+// The angle from `from` to `to`, anticlockwise looking down `normal`,
+// in (-pi, pi].
+pub fn signed_angle(from: &Vec3, to: &Vec3, normal: &Vec3) -> f64 {
+    normal.norm().dot(&from.cross(to)).atan2(from.dot(to))
+}
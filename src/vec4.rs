@@ -0,0 +1,66 @@
+//
+// vec4.rs: A 4D vector, for tracing through 3D spaces embedded in 4D.
+// The same as Vec3, minus the cross product. Length and
+// normalisation come from the Vector trait.
+//
+
+use crate::vec3::*;
+
+#[derive(Clone, Debug)]
+pub struct Vec4 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Vec4 {
+    pub fn zero() -> Vec4 {
+        Vec4 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 0.0,
+        }
+    }
+
+    // Drop w, projecting into 3D for drawing.
+    pub fn xyz(&self) -> Vec3 {
+        Vec3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+
+    pub fn scale(&self, m: f64) -> Vec4 {
+        Vec4 {
+            x: self.x * m,
+            y: self.y * m,
+            z: self.z * m,
+            w: self.w * m,
+        }
+    }
+
+    pub fn add(&self, rhs: &Vec4) -> Vec4 {
+        Vec4 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+            w: self.w + rhs.w,
+        }
+    }
+
+    pub fn sub(&self, rhs: &Vec4) -> Vec4 {
+        Vec4 {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+            w: self.w - rhs.w,
+        }
+    }
+
+    pub fn dot(&self, rhs: &Vec4) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+}
@@ -0,0 +1,93 @@
+//
+// vector.rs: A trait over the vector types, so that code that doesn't
+// care how many dimensions it's working in, such as stepping along an
+// implicit surface, can be written once.
+//
+
+use crate::vec3::*;
+use crate::vec4::*;
+
+// len is the vector's length, not a number of elements.
+#[allow(clippy::len_without_is_empty)]
+pub trait Vector: Clone {
+    // Number of components.
+    const DIM: usize;
+
+    fn zero() -> Self;
+
+    // The unit vector along the idx-th axis.
+    fn axis(idx: usize) -> Self;
+
+    fn add(&self, rhs: &Self) -> Self;
+    fn sub(&self, rhs: &Self) -> Self;
+    fn scale(&self, m: f64) -> Self;
+    fn dot(&self, rhs: &Self) -> f64;
+
+    fn len(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    fn norm(&self) -> Self {
+        self.scale(self.len().recip())
+    }
+}
+
+impl Vector for Vec3 {
+    const DIM: usize = 3;
+
+    fn zero() -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn axis(idx: usize) -> Vec3 {
+        let mut v = Vec3::zero();
+        *[&mut v.x, &mut v.y, &mut v.z][idx] = 1.0;
+        v
+    }
+
+    fn add(&self, rhs: &Vec3) -> Vec3 {
+        Vec3::add(self, rhs)
+    }
+
+    fn sub(&self, rhs: &Vec3) -> Vec3 {
+        Vec3::sub(self, rhs)
+    }
+
+    fn scale(&self, m: f64) -> Vec3 {
+        Vec3::scale(self, m)
+    }
+
+    fn dot(&self, rhs: &Vec3) -> f64 {
+        Vec3::dot(self, rhs)
+    }
+}
+
+impl Vector for Vec4 {
+    const DIM: usize = 4;
+
+    fn zero() -> Vec4 {
+        Vec4::zero()
+    }
+
+    fn axis(idx: usize) -> Vec4 {
+        let mut v = Vec4::zero();
+        *[&mut v.x, &mut v.y, &mut v.z, &mut v.w][idx] = 1.0;
+        v
+    }
+
+    fn add(&self, rhs: &Vec4) -> Vec4 {
+        Vec4::add(self, rhs)
+    }
+
+    fn sub(&self, rhs: &Vec4) -> Vec4 {
+        Vec4::sub(self, rhs)
+    }
+
+    fn scale(&self, m: f64) -> Vec4 {
+        Vec4::scale(self, m)
+    }
+
+    fn dot(&self, rhs: &Vec4) -> f64 {
+        Vec4::dot(self, rhs)
+    }
+}
@@ -0,0 +1,344 @@
+//
+// wormhole.rs: A CPU raytracer for an Ellis wormhole, rendering what a
+// camera near it would see, with no GPU involved.
+//
+// The Ellis wormhole's metric is
+//
+//   ds^2 = -dt^2 + dl^2 + (l^2 + b^2) (dtheta^2 + sin^2 theta dphi^2)
+//
+// where l runs from -infinity in one universe to +infinity in the
+// other, through a spherical throat of radius b at l = 0. As g_tt is
+// constant, light follows geodesics of the spatial part, so this is
+// the same problem as tracing geodesics on a surface, one dimension
+// up.
+//
+// By symmetry, each ray stays in a plane through the centre of the
+// throat, and what happens to it depends only on the angle it starts
+// at to the radial direction. So we trace one ray per angle, in the
+// plane's (l, phi) chart (metric.rs's EllisPlane), and look each pixel
+// up in the resulting table.
+//
+
+use crate::geodesic::{self, State};
+use crate::metric::{self, EllisPlane};
+use crate::vec3::*;
+
+// Number of starting angles, between straight out and straight in, to
+// trace rays for.
+const ANGLES: usize = 2048;
+
+// Step sizes and tolerance for the adaptive integrator. Steps can be
+// large away from the throat, where space is nearly flat.
+const INITIAL_STEP: f64 = 0.01;
+const MAX_STEP: f64 = 0.5;
+const TOLERANCE: f64 = 1.0e-9;
+
+// Give up on rays that take more steps than this, which are circling
+// the throat.
+const MAX_STEPS: usize = 5_000;
+
+// Rays have escaped once this many throat radii away, beyond which
+// they're treated as going straight.
+const ESCAPE: f64 = 50.0;
+
+////////////////////////////////////////////////////////////////////////
+// Tracing rays within the plane.
+//
+
+// Where a ray ends up: Which universe (the one with l > 0 or not), and
+// the direction it's heading in, as an angle within its plane from
+// the camera's outward radial direction.
+#[derive(Clone, Copy, Debug)]
+struct Outcome {
+    upper: bool,
+    angle: f64,
+}
+
+// Trace a ray from the camera at l0, leaving at angle beta to the
+// outward radial direction. None if it never escapes.
+fn trace(plane: &EllisPlane, l0: f64, beta: f64) -> Option<Outcome> {
+    let b = plane.throat;
+    let radius = |l: f64| (l * l + b * b).sqrt();
+    let outward = l0.signum();
+    let mut state = State {
+        p: Vec3 {
+            x: l0,
+            y: 0.0,
+            z: 0.0,
+        },
+        // Unit speed: l'^2 + r^2 phi'^2 = 1.
+        v: Vec3 {
+            x: outward * beta.cos(),
+            y: beta.sin() / radius(l0),
+            z: 0.0,
+        },
+    };
+    let eqn = metric::Geodesic(plane);
+    let mut adaptive = geodesic::Adaptive::new(INITIAL_STEP, MAX_STEP, TOLERANCE);
+    let escape = (ESCAPE * b).max(2.0 * l0.abs());
+    for _ in 0..MAX_STEPS {
+        let (l, phi) = (state.p.x, state.p.y);
+        if l.abs() > escape {
+            // Far enough out to be heading in a straight line. Its
+            // direction is the position angle, turned by the angle
+            // between the velocity and the radial direction.
+            let radial = state.v.x * l.signum();
+            let tangential = radius(l) * state.v.y;
+            return Some(Outcome {
+                upper: l > 0.0,
+                angle: phi + tangential.atan2(radial),
+            });
+        }
+        state = adaptive.step(&eqn, &state)?;
+    }
+    None
+}
+
+////////////////////////////////////////////////////////////////////////
+// Rendering.
+//
+
+// Where the camera is, and what it looks at. Angles are in degrees.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub throat: f64,
+    // The camera's l coordinate. Negative puts it in the other
+    // universe.
+    pub distance: f64,
+    // Turn and tilt away from looking straight at the throat.
+    pub yaw: f64,
+    pub pitch: f64,
+    // Vertical field of view.
+    pub fov: f64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            throat: 1.0,
+            distance: 4.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 60.0,
+            width: 640,
+            height: 480,
+        }
+    }
+}
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // Row by row, from the top left.
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    // Write as a binary PPM file, about the simplest image format
+    // there is.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_ppm(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for p in self.pixels.iter() {
+            w.write_all(p)?;
+        }
+        Ok(())
+    }
+}
+
+// Render the view from the camera.
+pub fn render(camera: &Camera) -> Image {
+    let plane = EllisPlane {
+        throat: camera.throat,
+    };
+    let table = (0..ANGLES)
+        .map(|idx| {
+            let beta = std::f64::consts::PI * idx as f64 / (ANGLES - 1) as f64;
+            trace(&plane, camera.distance, beta)
+        })
+        .collect::<Vec<_>>();
+
+    // The camera's frame, in the sky's coordinates, where +X is
+    // radially outward from the throat. With no yaw or pitch it looks
+    // along -X, towards the throat.
+    let (yaw, pitch) = (camera.yaw.to_radians(), camera.pitch.to_radians());
+    let forward = Vec3 {
+        x: -pitch.cos() * yaw.cos(),
+        y: pitch.cos() * yaw.sin(),
+        z: pitch.sin(),
+    };
+    let right = Vec3 {
+        x: yaw.sin(),
+        y: yaw.cos(),
+        z: 0.0,
+    };
+    let up = right.cross(&forward);
+    let half_height = (camera.fov.to_radians() * 0.5).tan();
+    let half_width = half_height * camera.width as f64 / camera.height as f64;
+
+    let mut pixels = Vec::with_capacity(camera.width * camera.height);
+    for j in 0..camera.height {
+        for i in 0..camera.width {
+            let sx = (2.0 * (i as f64 + 0.5) / camera.width as f64 - 1.0) * half_width;
+            let sy = (1.0 - 2.0 * (j as f64 + 0.5) / camera.height as f64) * half_height;
+            let d = forward.add(&right.scale(sx)).add(&up.scale(sy)).norm();
+            pixels.push(shade(&table, &d));
+        }
+    }
+    Image {
+        width: camera.width,
+        height: camera.height,
+        pixels,
+    }
+}
+
+// Render the view from the camera, and write it to the named file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_to_file(camera: &Camera, path: &str) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    render(camera).write_ppm(&mut file)
+}
+
+// The colour seen looking in direction d from the camera.
+fn shade(table: &[Option<Outcome>], d: &Vec3) -> [u8; 3] {
+    let beta = d.x.clamp(-1.0, 1.0).acos();
+    let pos = beta / std::f64::consts::PI * (table.len() - 1) as f64;
+    let idx = (pos as usize).min(table.len() - 2);
+    let fract = pos - idx as f64;
+    let outcome = match (table[idx], table[idx + 1]) {
+        // Interpolate, unless the rays either side went very
+        // different ways, as happens near the throat's edge.
+        (Some(a), Some(b)) if a.upper == b.upper && (a.angle - b.angle).abs() < 0.1 => {
+            Some(Outcome {
+                upper: a.upper,
+                angle: a.angle + (b.angle - a.angle) * fract,
+            })
+        }
+        (a, b) => {
+            if fract < 0.5 {
+                a
+            } else {
+                b
+            }
+        }
+    };
+    let Some(outcome) = outcome else {
+        // Trapped circling the throat.
+        return [0, 0, 0];
+    };
+
+    // The ray's plane contains the radial direction and the ray's
+    // initial sideways direction.
+    let sideways = Vec3 { x: 0.0, ..*d };
+    let sideways = if sideways.len() > 1.0e-12 {
+        sideways.norm()
+    } else {
+        Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    };
+    let radial = Vec3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    let dir = radial
+        .scale(outcome.angle.cos())
+        .add(&sideways.scale(outcome.angle.sin()));
+    sky(outcome.upper, &dir)
+}
+
+// Procedural skies for the two universes: A checkerboard in longitude
+// and latitude, blue on our side and orange on the other, scattered
+// with stars.
+fn sky(upper: bool, d: &Vec3) -> [u8; 3] {
+    const CELLS: f64 = 12.0;
+    const STAR_CELLS: f64 = 600.0;
+    const STAR_DENSITY: u64 = 400;
+
+    let lon = d.y.atan2(d.x) / std::f64::consts::PI;
+    let lat = d.z.clamp(-1.0, 1.0).asin() / std::f64::consts::PI;
+
+    // Cheap hash of the star cell, to scatter stars around.
+    let (u, v) = (
+        ((lon + 1.0) * STAR_CELLS) as u64,
+        ((lat + 0.5) * STAR_CELLS) as u64,
+    );
+    let hash = (u.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ v.wrapping_mul(0xc2b2_ae3d_27d4_eb4f))
+        .wrapping_mul(0x1656_67b1_9e37_79f9);
+    if (hash >> 32) % STAR_DENSITY == 0 {
+        return [255, 255, 255];
+    }
+
+    let check = (((lon + 1.0) * CELLS) as i64 + ((lat + 0.5) * CELLS) as i64) % 2 == 0;
+    match (upper, check) {
+        (true, true) => [60, 110, 220],
+        (true, false) => [200, 215, 240],
+        (false, true) => [230, 120, 30],
+        (false, false) => [90, 20, 20],
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// The view in the UI: The camera's controls, and its latest picture.
+//
+
+pub struct WormholeView {
+    camera: Camera,
+    texture: Option<egui::TextureHandle>,
+}
+
+impl Default for WormholeView {
+    fn default() -> Self {
+        WormholeView {
+            // Small, so that rendering stays quick in debug builds.
+            camera: Camera {
+                width: 320,
+                height: 240,
+                ..Camera::default()
+            },
+            texture: None,
+        }
+    }
+}
+
+impl WormholeView {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let camera = &mut self.camera;
+        ui.add(egui::Slider::new(&mut camera.throat, 0.1..=2.0).text("Throat radius"));
+        ui.add(egui::Slider::new(&mut camera.distance, -20.0..=20.0).text("Distance"));
+        ui.add(egui::Slider::new(&mut camera.yaw, -180.0..=180.0).text("Yaw"));
+        ui.add(egui::Slider::new(&mut camera.pitch, -90.0..=90.0).text("Pitch"));
+        ui.add(egui::Slider::new(&mut camera.fov, 10.0..=150.0).text("Field of view"));
+        ui.horizontal(|ui| {
+            if ui.button("Render").clicked() {
+                let image = render(camera);
+                let rgb = image.pixels.concat();
+                let texture = ui.ctx().load_texture(
+                    "wormhole",
+                    egui::ColorImage::from_rgb([image.width, image.height], &rgb),
+                    Default::default(),
+                );
+                self.texture = Some(texture);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save PPM").clicked() {
+                const PATH: &str = "wormhole.ppm";
+                match render_to_file(camera, PATH) {
+                    Ok(()) => log::info!("Wrote wormhole view to {}", PATH),
+                    Err(e) => log::error!("Couldn't write wormhole view to {}: {}", PATH, e),
+                }
+            }
+        });
+        if let Some(texture) = &self.texture {
+            ui.image(egui::load::SizedTexture::new(
+                texture.id(),
+                texture.size_vec2(),
+            ));
+        }
+    }
+}